@@ -0,0 +1,393 @@
+//! Animations drawn over the game scene, driven by the engine events: cleared rows flashing
+//! and collapsing, lock flashes, hard drop trails, particles and screen shake.
+
+use crate::theme::Theme;
+use egui::{pos2, vec2, Color32, Pos2, Rect, Rounding, Shape, Vec2};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tetris_core::events::EngineEvent;
+use tetris_core::geometry::Position;
+use tetris_core::storage::{check_version, load_json, save_json, StorageError};
+use tetris_core::tetris::DisplayState;
+use tetris_core::tetronimo::Kind;
+
+/// Version of the animation settings file format, bumped on every incompatible change
+const ANIMATIONS_VERSION: u32 = 1;
+
+/// Downward acceleration of the particles, in blocks per second squared
+const PARTICLE_GRAVITY: f32 = 30.;
+/// Amplitude of the shake of a hard drop and of a Tetris or a perfect clear, in blocks
+const DROP_SHAKE: f32 = 0.08;
+const CLEAR_SHAKE: f32 = 0.25;
+
+/// Durations of the animations, in milliseconds (0 turning one off)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationSettings {
+    version: u32,
+    /// true to turn off all the animations (for accessibility)
+    pub reduced_motion: bool,
+    /// flash of the cleared rows, then collapse of the blocks above them
+    pub line_clear: u64,
+    pub lock: u64,
+    pub hard_drop: u64,
+    /// particles of a Tetris or a perfect clear
+    pub particles: u64,
+    pub shake: u64,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            version: ANIMATIONS_VERSION,
+            reduced_motion: false,
+            line_clear: 300,
+            lock: 150,
+            hard_drop: 120,
+            particles: 800,
+            shake: 200,
+        }
+    }
+}
+
+impl AnimationSettings {
+    /// Read the settings from a file
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let settings: AnimationSettings = load_json(path)?;
+        check_version(settings.version, ANIMATIONS_VERSION)?;
+        Ok(settings)
+    }
+
+    /// Write the settings to a file
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+}
+
+/// Effect of an animation on the scene
+enum Effect {
+    // cleared rows (numbered as before their removal) flashing, then the blocks above
+    // falling into their place
+    LineClear {
+        rows: Vec<u8>,
+    },
+    // blocks of a locked tetronimo flashing
+    Lock {
+        blocks: Vec<Position>,
+    },
+    // trail of a hard dropped tetronimo, from its blocks before the drop
+    Trail {
+        kind: Kind,
+        blocks: Vec<Position>,
+        rows: u8,
+    },
+    Particles {
+        particles: Vec<Particle>,
+    },
+    // scene shaken, with an amplitude in blocks
+    Shake {
+        amplitude: f32,
+    },
+}
+
+/// Particle of a burst, in blocks
+struct Particle {
+    position: Pos2,
+    velocity: Vec2,
+    color: Color32,
+}
+
+/// Effect running for a duration
+struct Animation {
+    effect: Effect,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl Animation {
+    /// Return the part of the duration elapsed, from 0 to 1
+    fn progress(&self) -> f32 {
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.)
+    }
+}
+
+/// Animations running over the game scene
+#[derive(Default)]
+pub struct Animator {
+    pub settings: AnimationSettings,
+    animations: Vec<Animation>,
+}
+
+impl Animator {
+    pub fn new(settings: AnimationSettings) -> Self {
+        Self {
+            settings,
+            animations: Vec::new(),
+        }
+    }
+
+    /// Start the animations of an engine event, in the colors of the theme
+    pub fn start(&mut self, event: &EngineEvent, scene_area: Position, theme: &Theme) {
+        let settings = self.settings;
+        match event {
+            EngineEvent::HardDropped { kind, blocks, rows } if *rows > 0 => {
+                let trail = Effect::Trail {
+                    kind: *kind,
+                    blocks: blocks.clone(),
+                    rows: *rows,
+                };
+                self.push(trail, settings.hard_drop);
+                let shake = Effect::Shake {
+                    amplitude: DROP_SHAKE,
+                };
+                self.push(shake, settings.shake);
+            }
+            EngineEvent::PieceLocked { blocks, .. } => {
+                // the collapse is over once the next tetronimo lands
+                self.animations
+                    .retain(|animation| !matches!(animation.effect, Effect::LineClear { .. }));
+                let lock = Effect::Lock {
+                    blocks: blocks.clone(),
+                };
+                self.push(lock, settings.lock);
+            }
+            EngineEvent::LinesCleared {
+                rows,
+                perfect_clear,
+                ..
+            } => {
+                let clear = Effect::LineClear { rows: rows.clone() };
+                self.push(clear, settings.line_clear);
+                if rows.len() >= 4 || *perfect_clear {
+                    let particles = burst(rows, scene_area.x, theme.panel.accent);
+                    self.push(Effect::Particles { particles }, settings.particles);
+                    let shake = Effect::Shake {
+                        amplitude: CLEAR_SHAKE,
+                    };
+                    self.push(shake, settings.shake);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Run an effect for a duration in milliseconds, unless turned off
+    fn push(&mut self, effect: Effect, duration: u64) {
+        if self.settings.reduced_motion || duration == 0 {
+            return;
+        }
+        self.animations.push(Animation {
+            effect,
+            elapsed: Duration::ZERO,
+            duration: Duration::from_millis(duration),
+        });
+    }
+
+    /// Advance the animations by the time elapsed, dropping the finished ones
+    pub fn advance(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f32();
+        for animation in &mut self.animations {
+            animation.elapsed += elapsed;
+            if let Effect::Particles { particles } = &mut animation.effect {
+                for particle in particles {
+                    particle.position += particle.velocity * seconds;
+                    particle.velocity.y += PARTICLE_GRAVITY * seconds;
+                }
+            }
+        }
+        self.animations
+            .retain(|animation| animation.elapsed < animation.duration);
+    }
+
+    /// Return true while animations are running
+    pub fn is_running(&self) -> bool {
+        !self.animations.is_empty()
+    }
+
+    /// Return the offset of the shaken scene, in blocks
+    pub fn shake(&self) -> Vec2 {
+        self.animations
+            .iter()
+            .filter_map(|animation| match animation.effect {
+                Effect::Shake { amplitude } => {
+                    let time = animation.elapsed.as_secs_f32();
+                    let fading = amplitude * (1. - animation.progress());
+                    Some(vec2((time * 90.).sin(), (time * 70.).cos()) * fading)
+                }
+                _ => None,
+            })
+            .fold(Vec2::ZERO, |shake, offset| shake + offset)
+    }
+
+    /// Return the number of rows each dropped block of the state is drawn above its place,
+    /// while falling into the cleared rows (none if no rows are collapsing)
+    pub fn lifts(&self, state: &DisplayState) -> Vec<f32> {
+        let collapse = self.animations.iter().find_map(|animation| {
+            let Effect::LineClear { rows } = &animation.effect else {
+                return None;
+            };
+            Some((rows, animation.progress()))
+        });
+        let Some((rows, progress)) = collapse else {
+            return Vec::new();
+        };
+        // the blocks stay in place while the rows flash, then fall
+        let fall = (2. - 2. * progress).min(1.);
+        state
+            .blocks
+            .iter()
+            .map(|block| cleared_below(block.y, rows) as f32 * fall)
+            .collect()
+    }
+
+    /// Return the shapes of the animations over the scene, in the colors of the theme
+    pub fn shapes(
+        &self,
+        block_size: f32,
+        reference: Pos2,
+        scene_area: Position,
+        theme: &Theme,
+    ) -> Vec<Shape> {
+        // rectangle of an area of the scene, in blocks
+        let rect = |min: Pos2, size: Vec2| {
+            Rect::from_min_size(reference + min.to_vec2() * block_size, size * block_size)
+        };
+        let block = |block: &Position| rect(pos2(block.x as f32, block.y as f32), Vec2::splat(1.));
+        let mut shapes = Vec::new();
+        for animation in &self.animations {
+            let fading = 1. - animation.progress();
+            match &animation.effect {
+                Effect::LineClear { rows } if animation.progress() < 0.5 => {
+                    let color = Color32::from_white_alpha((255. * (2. * fading - 1.)) as u8);
+                    for &row in rows {
+                        let area = rect(pos2(0., row as f32), vec2(scene_area.x as f32, 1.));
+                        shapes.push(Shape::rect_filled(area, Rounding::none(), color));
+                    }
+                }
+                Effect::LineClear { .. } => {}
+                Effect::Lock { blocks } => {
+                    let color = Color32::from_white_alpha((160. * fading) as u8);
+                    for area in blocks.iter().map(block) {
+                        shapes.push(Shape::rect_filled(area, Rounding::none(), color));
+                    }
+                }
+                Effect::Trail { kind, blocks, rows } => {
+                    // a streak above each column of the tetronimo, down to where it landed
+                    let color = theme.block(Some(*kind)).fill.linear_multiply(0.4 * fading);
+                    for top in blocks.iter().filter(|top| {
+                        !blocks
+                            .iter()
+                            .any(|other| other.x == top.x && other.y < top.y)
+                    }) {
+                        let area = rect(pos2(top.x as f32, top.y as f32), vec2(1., *rows as f32));
+                        shapes.push(Shape::rect_filled(area, Rounding::none(), color));
+                    }
+                }
+                Effect::Particles { particles } => {
+                    for particle in particles {
+                        let area = rect(particle.position, Vec2::splat(0.2));
+                        let color = particle.color.linear_multiply(fading);
+                        shapes.push(Shape::rect_filled(area, Rounding::none(), color));
+                    }
+                }
+                Effect::Shake { .. } => {}
+            }
+        }
+        shapes
+    }
+}
+
+/// Return the particles bursting out of the cleared rows
+fn burst(rows: &[u8], width: u8, accent: Color32) -> Vec<Particle> {
+    let mut rng = rand::thread_rng();
+    rows.iter()
+        .flat_map(|&row| (0..width).map(move |column| (row, column)))
+        .flat_map(|(row, column)| [(row, column, Color32::WHITE), (row, column, accent)])
+        .map(|(row, column, color)| Particle {
+            position: pos2(column as f32 + 0.5, row as f32 + 0.5),
+            velocity: vec2(rng.gen_range(-4.0..4.0), rng.gen_range(-12.0..-4.0)),
+            color,
+        })
+        .collect()
+}
+
+/// Return the number of cleared rows (numbered as before their removal) that were below
+/// a block now in a row, i.e. the number of rows it fell
+fn cleared_below(row: u8, cleared: &[u8]) -> u8 {
+    (0..=cleared.len() as u8)
+        .find(|&fallen| {
+            row.checked_sub(fallen).is_some_and(|origin| {
+                !cleared.contains(&origin)
+                    && cleared.iter().filter(|&&other| other > origin).count() == fallen as usize
+            })
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::{cleared_below, AnimationSettings, Animator, ANIMATIONS_VERSION};
+    use crate::theme::Theme;
+    use std::time::Duration;
+    use tetris_core::events::EngineEvent;
+    use tetris_core::geometry::Position;
+    use tetris_core::storage::StorageError;
+
+    #[test]
+    fn cleared_below_test() {
+        // rows 17 and 19 cleared: the block of row 18 fell one row, the ones above two
+        let cleared = [19, 17];
+        assert_eq!(cleared_below(19, &cleared), 1);
+        assert_eq!(cleared_below(18, &cleared), 2);
+        assert_eq!(cleared_below(3, &cleared), 2);
+        assert_eq!(cleared_below(19, &[]), 0);
+    }
+
+    #[test]
+    fn animator_test() {
+        let scene_area = Position::new(10, 20);
+        let theme = Theme::default();
+        let tetris = EngineEvent::LinesCleared {
+            rows: vec![19, 18, 17, 16],
+            t_spin: false,
+            combo: 1,
+            perfect_clear: false,
+        };
+        let mut animator = Animator::default();
+        animator.start(&tetris, scene_area, &theme);
+        assert!(animator.is_running());
+        assert!(!animator
+            .shapes(25., Default::default(), scene_area, &theme)
+            .is_empty());
+        animator.advance(Duration::from_secs(1));
+        assert!(!animator.is_running());
+
+        // nothing moves with the reduced motion
+        let mut animator = Animator::new(AnimationSettings {
+            reduced_motion: true,
+            ..AnimationSettings::default()
+        });
+        animator.start(&tetris, scene_area, &theme);
+        assert!(!animator.is_running());
+    }
+
+    #[test]
+    fn persistence_test() {
+        let path = std::env::temp_dir().join("tetris-animations-test.json");
+        let mut settings = AnimationSettings {
+            lock: 0,
+            ..AnimationSettings::default()
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(AnimationSettings::load(&path).unwrap(), settings);
+
+        // a file of a later version is rejected
+        settings.version = ANIMATIONS_VERSION + 1;
+        settings.save(&path).unwrap();
+        let result = AnimationSettings::load(&path);
+        assert!(matches!(result, Err(StorageError::Version(_))));
+        let _ = std::fs::remove_file(path);
+    }
+}
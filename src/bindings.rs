@@ -0,0 +1,278 @@
+//! Key bindings of the game actions, with presets and persistence to a config file.
+
+use crate::input::Handling;
+use egui::Key;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tetris_core::storage::{check_version, load_json, save_json, StorageError};
+
+/// Version of the bindings file format, bumped on every incompatible change
+const BINDINGS_VERSION: u32 = 1;
+
+/// Game action bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Left,
+    Right,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+    Hold,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Left,
+        Action::Right,
+        Action::Rotate,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Hold,
+    ];
+
+    /// Return the name of the action shown to the user
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Left => "Move left",
+            Action::Right => "Move right",
+            Action::Rotate => "Rotate",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::Hold => "Hold",
+        }
+    }
+}
+
+/// Predefined set of bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Arrows,
+    Wasd,
+    Guideline,
+    Vim,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Arrows, Preset::Wasd, Preset::Guideline, Preset::Vim];
+
+    /// Return the name of the preset shown to the user
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::Arrows => "Arrows",
+            Preset::Wasd => "WASD",
+            Preset::Guideline => "Guideline",
+            Preset::Vim => "Vim",
+        }
+    }
+
+    /// Return the keys of each action, in the order of `Action::ALL`
+    fn keys(self) -> [&'static [Key]; 6] {
+        match self {
+            Preset::Arrows => [
+                &[Key::ArrowLeft],
+                &[Key::ArrowRight],
+                &[Key::ArrowUp],
+                &[Key::ArrowDown],
+                &[Key::Space],
+                &[Key::C],
+            ],
+            Preset::Wasd => [
+                &[Key::A],
+                &[Key::D],
+                &[Key::W],
+                &[Key::S],
+                &[Key::Space],
+                &[Key::Q],
+            ],
+            // the engine has a single rotation: the clockwise and counterclockwise keys both turn it
+            Preset::Guideline => [
+                &[Key::ArrowLeft],
+                &[Key::ArrowRight],
+                &[Key::ArrowUp, Key::X, Key::Z],
+                &[Key::ArrowDown],
+                &[Key::Space],
+                &[Key::C],
+            ],
+            Preset::Vim => [
+                &[Key::H],
+                &[Key::L],
+                &[Key::K],
+                &[Key::J],
+                &[Key::Space],
+                &[Key::C],
+            ],
+        }
+    }
+}
+
+/// Keys bound to each game action (any number of them, possibly none),
+/// along with the handling settings of the player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bindings {
+    version: u32,
+    keys: BTreeMap<Action, Vec<Key>>,
+    #[serde(default)]
+    pub handling: Handling,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::preset(Preset::Arrows)
+    }
+}
+
+impl Bindings {
+    /// Create the bindings of a preset
+    pub fn preset(preset: Preset) -> Self {
+        Self {
+            version: BINDINGS_VERSION,
+            keys: Action::ALL
+                .into_iter()
+                .zip(preset.keys())
+                .map(|(action, keys)| (action, keys.to_vec()))
+                .collect(),
+            handling: Handling::default(),
+        }
+    }
+
+    /// Create the bindings of a player of a local versus game
+    pub fn versus(player: usize) -> Self {
+        match player {
+            0 => Self::preset(Preset::Wasd),
+            _ => {
+                let mut bindings = Self::preset(Preset::Arrows);
+                bindings.keys.insert(Action::HardDrop, vec![Key::Enter]);
+                bindings.keys.insert(Action::Hold, vec![Key::Backspace]);
+                bindings
+            }
+        }
+    }
+
+    /// Read the bindings from a file
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let mut bindings: Bindings = load_json(path)?;
+        check_version(bindings.version, BINDINGS_VERSION)?;
+        // actions added since the file was written get their default keys
+        for (action, keys) in Self::default().keys {
+            bindings.keys.entry(action).or_insert(keys);
+        }
+        Ok(bindings)
+    }
+
+    /// Read the bindings from a file, using the default ones if it is missing or unreadable
+    /// (the error of an unreadable file is returned)
+    pub fn load_or_default(path: &Path) -> (Self, Option<StorageError>) {
+        match Self::load(path) {
+            Ok(bindings) => (bindings, None),
+            Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                (Self::default(), None)
+            }
+            Err(err) => (Self::default(), Some(err)),
+        }
+    }
+
+    /// Write the bindings to a file
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+
+    /// Return the keys bound to an action
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Bind a key to an action, returning the other actions it is also bound to
+    pub fn bind(&mut self, action: Action, key: Key) -> Vec<Action> {
+        let keys = self.keys.entry(action).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        self.actions(key)
+            .into_iter()
+            .filter(|&other| other != action)
+            .collect()
+    }
+
+    /// Remove a key from an action
+    pub fn unbind(&mut self, action: Action, key: Key) {
+        if let Some(keys) = self.keys.get_mut(&action) {
+            keys.retain(|&bound| bound != key);
+        }
+    }
+
+    /// Return the actions a key is bound to
+    pub fn actions(&self, key: Key) -> Vec<Action> {
+        self.keys
+            .iter()
+            .filter(|(_, keys)| keys.contains(&key))
+            .map(|(&action, _)| action)
+            .collect()
+    }
+
+    /// Return the keys bound to more than one action, with their actions
+    pub fn conflicts(&self) -> Vec<(Key, Vec<Action>)> {
+        let mut keys: Vec<Key> = self.keys.values().flatten().copied().collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .map(|key| (key, self.actions(key)))
+            .filter(|(_, actions)| actions.len() > 1)
+            .collect()
+    }
+
+    /// Return true if a key of the action was pressed in the Context
+    pub fn pressed(&self, ctx: &egui::Context, action: Action) -> bool {
+        let input = ctx.input();
+        self.keys(action).iter().any(|&key| input.key_pressed(key))
+    }
+
+    /// Return true if a key of the action is held down in the Context
+    pub fn down(&self, ctx: &egui::Context, action: Action) -> bool {
+        let input = ctx.input();
+        self.keys(action).iter().any(|&key| input.key_down(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bindings::{Action, Bindings, Preset};
+    use egui::Key;
+
+    #[test]
+    fn bind_test() {
+        let mut bindings = Bindings::preset(Preset::Vim);
+        assert_eq!(bindings.keys(Action::Left), &[Key::H]);
+        assert!(bindings.conflicts().is_empty());
+
+        // a key can be bound to several actions, reported as conflicts
+        assert!(bindings.bind(Action::Left, Key::A).is_empty());
+        assert_eq!(bindings.bind(Action::Rotate, Key::H), vec![Action::Left]);
+        assert_eq!(bindings.keys(Action::Rotate), &[Key::K, Key::H]);
+        assert_eq!(
+            bindings.conflicts(),
+            vec![(Key::H, vec![Action::Left, Action::Rotate])]
+        );
+        bindings.unbind(Action::Left, Key::H);
+        assert!(bindings.conflicts().is_empty());
+        assert_eq!(bindings.keys(Action::Left), &[Key::A]);
+    }
+
+    #[test]
+    fn persistence_test() {
+        let path = std::env::temp_dir().join("tetris-bindings-test.json");
+        let bindings = Bindings::preset(Preset::Guideline);
+        bindings.save(&path).unwrap();
+        assert_eq!(Bindings::load(&path).unwrap(), bindings);
+
+        // an action missing from the file gets its default keys, unlike an unbound one
+        let mut older = bindings.clone();
+        older.keys.remove(&Action::Hold);
+        older.keys.insert(Action::Rotate, Vec::new());
+        older.save(&path).unwrap();
+        let loaded = Bindings::load(&path).unwrap();
+        assert_eq!(loaded.keys(Action::Hold), &[Key::C]);
+        assert!(loaded.keys(Action::Rotate).is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
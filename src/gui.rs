@@ -1,51 +1,303 @@
-use crate::geometry::Position;
-use crate::tetris::{DisplayState, Event, Tetris};
+use crate::animation::{AnimationSettings, Animator};
+use crate::bindings::{Action, Bindings, Preset};
+use crate::input::{InputHandler, Keys};
+use crate::skin::SkinTexture;
+use crate::theme::Theme;
 use crate::utils::*;
+use crate::window::WindowGeometry;
 use eframe::egui;
-use egui::{mutex::Mutex, vec2, Color32, Context, Vec2};
-use std::sync::Arc;
+use egui::{vec2, Color32, Context, Vec2};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+use tetris_core::bot::{Autopilot, Sparring, Strength};
+use tetris_core::clock::{Clock, SystemClock};
+use tetris_core::events::EngineEvent;
+use tetris_core::geometry::Position;
+use tetris_core::highscores::{HighScoreEntry, HighScores, MARATHON};
+use tetris_core::net::{NetError, NetVersus, Netcode};
+use tetris_core::replay::{Replay, ReplayPlayer};
+use tetris_core::rollback::{RollbackVersus, TcpTransport};
+use tetris_core::ruleset::Ruleset;
+use tetris_core::session::Session;
+use tetris_core::spectate::{SpectatorClient, SpectatorServer};
+use tetris_core::tetris::{DisplayState, Event, Tetris};
+use tetris_core::tetronimo::Kind;
+use tetris_core::versus::Versus;
 
 pub struct GameInterface {
     // Texture to hold the image of tetris logo
     logo: egui::TextureHandle,
-    // Game engine
-    engine: Arc<Mutex<Tetris>>,
-    // display size of each tetris block
+    // Game session, owning the game engine
+    session: Session<SystemClock>,
+    // keys held by the player
+    input: InputHandler,
+    // display size of each tetris block, fitting the board in the window
     block_size: f32,
+    // geometry of the window, saved on exit
+    window: Option<WindowGeometry>,
+    // replay being played back instead of the game engine, if any
+    playback: Option<ReplayPlayer>,
+    // last message to display to the user (e.g. replay file errors)
+    message: Option<String>,
+    // local leaderboard
+    high_scores: HighScores,
+    // true while the high scores window is open
+    show_high_scores: bool,
+    // new high score waiting for the player name, if any
+    score_prompt: Option<HighScoreEntry>,
+    // seed of the last finished game checked for a high score
+    checked_game: Option<u64>,
+    // channel receiving the events of the game engine
+    engine_events: Receiver<EngineEvent>,
+    // announcement of the last notable engine event (e.g. "Tetris!")
+    announcement: Option<String>,
+    // animations of the engine events over the game scene
+    animator: Animator,
+    // two-player game played instead of the session, if any
+    versus: Option<VersusMatch>,
+    // strength of the bot of the next versus game or demo
+    bot_strength: Strength,
+    // bot playing the session instead of the player (demo mode), if any
+    demo: Option<Autopilot>,
+    // address to host or join a networked versus game on
+    address: String,
+    // netcode of the networked versus games hosted or joined
+    netcode: Netcode,
+    // networked versus game being hosted or joined, while connecting
+    connecting: Option<Receiver<Result<VersusGame, NetError>>>,
+    // server broadcasting the game to spectators, if any
+    broadcast: Option<SpectatorServer>,
+    // remote game watched instead of the session, if any
+    spectating: Option<Spectating>,
+    // keys bound to the game actions
+    bindings: Bindings,
+    // true while the controls window is open
+    show_controls: bool,
+    // action waiting for a key press to be bound to it, if any
+    rebinding: Option<Action>,
+    // built-in and user themes, and the index of the one in use
+    themes: Vec<Theme>,
+    theme: usize,
+    // skin of the theme in use loaded in a texture, if any
+    skin: Option<SkinTexture>,
 }
 
+/// Remote game watched, with its last display state received
+struct Spectating {
+    client: SpectatorClient,
+    state: DisplayState,
+}
+
+/// Two-player game: both players on this keyboard, or one of them on a remote client
+enum VersusGame {
+    Local(Box<Versus>),
+    Network(Box<NetVersus>),
+    Rollback(Box<RollbackVersus<TcpTransport>>),
+}
+
+/// Two-player game, with its clock, and the keys bound and held of each player
+struct VersusMatch {
+    game: VersusGame,
+    clock: SystemClock,
+    bindings: [Bindings; 2],
+    inputs: [InputHandler; 2],
+    // bot playing the second player of a local game, if any
+    sparring: Option<Sparring>,
+}
+
+impl VersusMatch {
+    fn new(game: VersusGame, bindings: [Bindings; 2]) -> Self {
+        Self {
+            game,
+            clock: SystemClock::new(),
+            bindings,
+            inputs: Default::default(),
+            sparring: None,
+        }
+    }
+
+    /// Create a game against a bot, the player using their own keys
+    fn against_bot(strength: Strength, bindings: Bindings) -> Self {
+        let mut versus = Self::local();
+        versus.bindings[0] = bindings;
+        versus.sparring = Some(Sparring::new(strength, random_seed(), 1));
+        versus
+    }
+
+    /// Create a game for two players on this keyboard
+    fn local() -> Self {
+        let game = Versus::new(Ruleset::default(), random_seed());
+        Self::new(
+            VersusGame::Local(Box::new(game)),
+            [Bindings::versus(0), Bindings::versus(1)],
+        )
+    }
+
+    /// Return the versus game, whether local or networked
+    fn versus(&self) -> &Versus {
+        match &self.game {
+            VersusGame::Local(versus) => versus,
+            VersusGame::Network(network) => network.versus(),
+            VersusGame::Rollback(network) => network.versus(),
+        }
+    }
+}
+
+const BASE_BLOCK_SIZE: f32 = 25.0;
+const MIN_BLOCK_SIZE: f32 = 8.0;
 const FRAME_BORDER: f32 = 1.25;
 const STATS_PANEL_WIDTH: f32 = 4.0;
+const GARBAGE_METER_WIDTH: f32 = 0.5;
+const HEADER_HEIGHT: f32 = 2.0;
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+const REPLAY_PATH: &str = "./replay.json";
+const SAVE_PATH: &str = "./savegame.json";
+const HIGHSCORES_PATH: &str = "./highscores.json";
+const BINDINGS_PATH: &str = "./bindings.json";
+pub const WINDOW_PATH: &str = "./window.json";
+const THEMES_DIR: &str = "./themes";
+const ANIMATIONS_PATH: &str = "./animations.json";
+// errors of the last close, shown at the next launch
+const CLOSE_ERRORS_PATH: &str = "./close_errors.txt";
 
 impl eframe::App for GameInterface {
     /// Main thread drawing function (event entry point)
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        // get the user input event in context, if any
-        let input_event = get_input_from_context(ctx);
+        self.check_connection();
+        if self.versus.is_some() {
+            return self.update_versus(ctx, frame);
+        }
+        if self.spectating.is_some() {
+            return self.update_spectating(ctx, frame);
+        }
+        if self.connecting.is_some() {
+            ctx.request_repaint();
+        }
+
+        // get the user input events in context, if any (none while a key is being bound)
+        let capturing = self.rebinding.is_some();
+        let keys = match capturing {
+            true => Keys::default(),
+            false => Keys::from_context(ctx, &self.bindings),
+        };
+        let mut inputs = self.input.update(keys);
+        // the bot plays the demo games instead, starting a new one once over
+        if let Some(autopilot) = &mut self.demo {
+            if self.session.engine().game_over() {
+                self.session.restart();
+            }
+            let elapsed = Duration::from_secs_f32(ctx.input().stable_dt);
+            inputs = autopilot.next_events(self.session.engine(), elapsed);
+        }
 
-        // run the event and get the display state from the engine
-        let state = self.run_and_get_display_state(input_event);
+        // run the event and get the display state from the engine,
+        // or advance the replay playback and get its display state
+        let state = match &mut self.playback {
+            Some(player) => {
+                player.advance(Duration::from_secs_f32(ctx.input().stable_dt));
+                ctx.request_repaint();
+                player.engine().get_display_state()
+            }
+            None => self.run_and_get_display_state(&inputs),
+        };
+        // keep the game running, advanced by the clock at each frame
+        if self.session.is_running() && self.playback.is_none() {
+            ctx.request_repaint();
+        }
 
-        // set window size base on the game state area
-        frame.set_window_size(vec2(
-            (state.scene_area.x as f32 + FRAME_BORDER + STATS_PANEL_WIDTH) * self.block_size,
-            (state.scene_area.y as f32 + FRAME_BORDER) * self.block_size,
-        ));
+        // fit the game state area and the stats panel in the window
+        self.fit_window(
+            ctx,
+            frame,
+            vec2(
+                state.scene_area.x as f32 + FRAME_BORDER + STATS_PANEL_WIDTH,
+                state.scene_area.y as f32 + FRAME_BORDER,
+            ),
+        );
+
+        // send the state to the spectators
+        if let Some(server) = &mut self.broadcast {
+            if let Err(err) = server.broadcast(&state) {
+                self.message = Some(err.to_string());
+                self.broadcast = None;
+            }
+        }
+
+        // announce and animate the notable engine events
+        self.animator
+            .advance(Duration::from_secs_f32(ctx.input().stable_dt));
+        for event in self.engine_events.try_iter() {
+            if let Some(announcement) = announce(&event) {
+                self.announcement = Some(announcement);
+            }
+            let theme = &self.themes[self.theme];
+            self.animator.start(&event, state.scene_area, theme);
+        }
+        if self.animator.is_running() {
+            ctx.request_repaint();
+        }
+
+        // check the result of a finished game (not a replay nor a demo) for the leaderboard
+        if state.game_over && self.playback.is_none() && self.demo.is_none() {
+            self.check_high_score(&state);
+        }
 
         // draw the state
         egui::CentralPanel::default().show(ctx, |ui| {
             self.show_stats(ui, &state);
             self.show_game(ui, &state);
         });
+        self.show_high_scores(ctx, state.scene_area);
+        self.show_controls(ctx);
+    }
+
+    /// Suspend the game on exit: save it to be resumed on the next launch
+    fn on_close_event(&mut self) -> bool {
+        let mut errors = Vec::new();
+        let game = self.session.engine();
+        let path = std::path::Path::new(SAVE_PATH);
+        if game.game_over() || self.demo.is_some() {
+            // nothing to resume, discard any previous save
+            let _ = std::fs::remove_file(path);
+        } else if let Err(err) = game.save(path) {
+            errors.push(format!("Game not saved, {err}"));
+        }
+        if let Some(window) = self.window {
+            if let Err(err) = window.save(std::path::Path::new(WINDOW_PATH)) {
+                errors.push(format!("Window geometry not saved, {err}"));
+            }
+        }
+        // there is no window left to show the errors in, keep them for the next launch
+        if !errors.is_empty() {
+            let _ = std::fs::write(CLOSE_ERRORS_PATH, errors.join("\n"));
+        }
+        true
     }
 }
 
 impl GameInterface {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // set the font style and size
-        set_font_style(cc);
+        // load the leaderboard, reporting (but otherwise ignoring) an unreadable file
+        let (high_scores, error) =
+            HighScores::load_or_default(std::path::Path::new(HIGHSCORES_PATH));
+        let (bindings, bindings_error) =
+            Bindings::load_or_default(std::path::Path::new(BINDINGS_PATH));
 
-        Self {
+        // load the user themes after the built-in ones
+        let (user_themes, theme_errors) = Theme::load_dir(std::path::Path::new(THEMES_DIR));
+        let mut themes = Theme::builtin();
+        themes.extend(user_themes);
+
+        // report the errors of the last close, once
+        let close_errors = std::fs::read_to_string(CLOSE_ERRORS_PATH).ok();
+        let _ = std::fs::remove_file(CLOSE_ERRORS_PATH);
+
+        // Create the game engine, resuming the game suspended in the last session if any
+        let mut engine = Tetris::load(std::path::Path::new(SAVE_PATH))
+            .unwrap_or_else(|_| Tetris::new(bindings.handling.apply(Ruleset::default())));
+        let engine_events = engine.subscribe();
+
+        let mut interface = Self {
             // Load the logo texture (only once, at object creation)
             logo: cc.egui_ctx.load_texture(
                 "logo",
@@ -53,73 +305,806 @@ impl GameInterface {
                     .unwrap_or_default(),
                 Default::default(),
             ),
-            // Create the game engine to be shared via a mutex
-            engine: Arc::new(Mutex::new(Tetris::new(Position::new(10, 20)))),
-            block_size: 25.,
+            session: Session::new(engine, SystemClock::new()),
+            input: InputHandler::default(),
+            block_size: BASE_BLOCK_SIZE,
+            window: None,
+            playback: None,
+            message: close_errors
+                .or(error.map(|err| format!("High scores reset, {err}")))
+                .or(bindings_error.map(|err| format!("Default controls, {err}")))
+                .or(theme_errors
+                    .first()
+                    .map(|err| format!("Theme skipped, {err}"))),
+            high_scores,
+            show_high_scores: false,
+            score_prompt: None,
+            checked_game: None,
+            engine_events,
+            announcement: None,
+            animator: Animator::new(
+                AnimationSettings::load(std::path::Path::new(ANIMATIONS_PATH)).unwrap_or_default(),
+            ),
+            versus: None,
+            bot_strength: Strength::Normal,
+            demo: None,
+            address: DEFAULT_ADDRESS.to_owned(),
+            netcode: Netcode::Rollback,
+            connecting: None,
+            broadcast: None,
+            spectating: None,
+            bindings,
+            show_controls: false,
+            rebinding: None,
+            themes,
+            theme: 0,
+            skin: None,
+        };
+        // set the fonts, style and skin of the first theme
+        interface.set_theme(&cc.egui_ctx, 0);
+        interface
+    }
+
+    /// Compute the block size fitting a layout (in blocks) in the window, keeping its aspect
+    /// ratio, and scale the fonts to match. F11 toggles the fullscreen mode
+    fn fit_window(&mut self, ctx: &Context, frame: &mut eframe::Frame, layout: Vec2) {
+        let geometry = WindowGeometry::of(frame);
+        if ctx.input().key_pressed(egui::Key::F11) {
+            frame.set_fullscreen(!geometry.fullscreen);
+        }
+        self.window = Some(geometry);
+
+        // sizes are in points, already scaled by egui for HiDPI screens
+        let available = ctx.input().screen_rect().size();
+        let block_size = (available.x / layout.x)
+            .min(available.y / layout.y)
+            .max(MIN_BLOCK_SIZE);
+        if block_size != self.block_size {
+            self.block_size = block_size;
+            ctx.set_style(self.theme().style(block_size / BASE_BLOCK_SIZE));
+        }
+    }
+
+    /// Return the theme in use
+    fn theme(&self) -> &Theme {
+        &self.themes[self.theme]
+    }
+
+    /// Switch to another theme, setting its fonts, style and skin (the bundled font and
+    /// the flat colors are used when its files are unreadable)
+    fn set_theme(&mut self, ctx: &Context, index: usize) {
+        self.theme = index;
+        if let Err(err) = set_fonts(ctx, self.theme()) {
+            self.message = Some(format!("Default font, {err}"));
         }
+        self.skin = None;
+        if let Some(skin) = &self.themes[index].skin {
+            match SkinTexture::load(ctx, skin) {
+                Ok(texture) => self.skin = Some(texture),
+                Err(err) => self.message = Some(format!("Flat blocks, {err}")),
+            }
+        }
+        ctx.set_style(self.theme().style(self.block_size / BASE_BLOCK_SIZE));
     }
 
-    /// Access the game engine to run an event and retrive the resulting game state
-    fn run_and_get_display_state(&mut self, input: Option<Event>) -> DisplayState {
-        // Lock the engine in this thread
-        let mut game = self.engine.lock();
+    /// Run the input events and advance the game session, then retrive the resulting game state
+    fn run_and_get_display_state(&mut self, inputs: &[Event]) -> DisplayState {
+        // execute every user input of the frame, in order, before advancing the game
+        // (so they all apply to the same engine frame)
+        inputs.iter().for_each(|&event| self.session.run(event));
 
-        // execute user input, if any
-        input.and_then(|event| game.run(event));
+        // advance the game by the time elapsed since the previous frame
+        self.session.update();
 
-        // return the display state (and release lock on engine)
-        game.get_display_state()
+        // return the display state
+        self.session.engine().get_display_state()
+    }
+
+    /// Run the inputs of both players of the versus game, advance it and draw it
+    fn update_versus(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        let Some(versus) = &mut self.versus else {
+            return;
+        };
+        let elapsed = versus.clock.elapsed();
+        let mut failure = None;
+        match &mut versus.game {
+            VersusGame::Local(game) => {
+                let sparring = &mut versus.sparring;
+                for (player, keys) in versus.bindings.iter().enumerate() {
+                    if sparring.as_ref().map(Sparring::player) == Some(player) {
+                        continue;
+                    }
+                    for event in versus.inputs[player].update(Keys::from_context(ctx, keys)) {
+                        game.run(player, event);
+                    }
+                }
+                if let Some(sparring) = sparring {
+                    sparring.tick(game, elapsed);
+                }
+                game.tick(elapsed);
+            }
+            // the local player of a networked game uses the keys of the first player
+            VersusGame::Network(game) => {
+                let keys = &versus.bindings[0];
+                for event in versus.inputs[0].update(Keys::from_context(ctx, keys)) {
+                    game.run(event);
+                }
+                failure = game.tick(elapsed).err();
+            }
+            VersusGame::Rollback(game) => {
+                let keys = &versus.bindings[0];
+                for event in versus.inputs[0].update(Keys::from_context(ctx, keys)) {
+                    game.run(event);
+                }
+                failure = game.tick(elapsed).err();
+            }
+        }
+        let local = match &versus.game {
+            VersusGame::Local(_) => None,
+            VersusGame::Network(game) => Some(game.local_player()),
+            VersusGame::Rollback(game) => Some(game.local_player()),
+        };
+        let bot = versus.sparring.as_ref().map(Sparring::strength);
+        let game = versus.versus();
+        if !game.is_over() {
+            ctx.request_repaint();
+        }
+        let states = [0, 1].map(|player| game.engine(player).get_display_state());
+        let incoming = [0, 1].map(|player| game.incoming(player));
+        let status = match (game.is_over(), game.winner(), local) {
+            (false, _, None) => match bot {
+                Some(strength) => format!("P2: {strength:?} bot"),
+                None => "P1: WASD + Space    P2: arrows + Enter".to_owned(),
+            },
+            (false, _, Some(player)) => format!("You are player {}", player + 1),
+            (true, Some(player), _) => format!("Player {} wins!", player + 1),
+            (true, None, _) => "Draw".to_owned(),
+        };
+
+        // fit both boards and their garbage meters in the window
+        let scene_area = states[0].scene_area;
+        let layout = vec2(
+            2. * (scene_area.x as f32 + GARBAGE_METER_WIDTH + FRAME_BORDER),
+            scene_area.y as f32 + FRAME_BORDER + HEADER_HEIGHT,
+        );
+        self.fit_window(ctx, frame, layout);
+        let block_size = self.block_size;
+        let theme = self.theme();
+        let skin = self.skin.as_ref();
+
+        let mut rematch = false;
+        let mut exit = false;
+        egui::TopBottomPanel::top("versus").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(status);
+                rematch = ui
+                    .add_enabled(local.is_none(), egui::Button::new("Rematch"))
+                    .clicked();
+                exit = ui.button("Exit").clicked();
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (state, incoming) in states.iter().zip(incoming) {
+                    show_garbage_meter(ui, incoming, scene_area, block_size);
+                    egui::Frame::canvas(ui.style()).show(ui, |ui| {
+                        let (response, painter) = ui.allocate_painter(
+                            vec2(scene_area.x as f32, scene_area.y as f32) * block_size,
+                            egui::Sense::hover(),
+                        );
+                        painter.extend(build_game_blocks(
+                            state,
+                            block_size,
+                            response.rect.left_top(),
+                            theme,
+                            skin,
+                            &[],
+                        ));
+                    });
+                }
+            });
+        });
+        if let Some(err) = failure {
+            self.message = Some(err.to_string());
+            self.versus = None;
+        } else if rematch {
+            self.versus = Some(match bot {
+                Some(strength) => VersusMatch::against_bot(strength, self.bindings.clone()),
+                None => VersusMatch::local(),
+            });
+        } else if exit {
+            self.versus = None;
+        }
+    }
+
+    /// Draw the last display state received from the watched game
+    fn update_spectating(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        let Some(spectating) = &mut self.spectating else {
+            return;
+        };
+        let failure = match spectating.client.latest() {
+            Ok(Some(state)) => {
+                spectating.state = state;
+                None
+            }
+            Ok(None) => None,
+            Err(err) => Some(err),
+        };
+        // keep polling for the states to come
+        ctx.request_repaint();
+
+        let Some(Spectating { state, .. }) = &self.spectating else {
+            return;
+        };
+        let layout = vec2(
+            state.scene_area.x as f32 + FRAME_BORDER,
+            state.scene_area.y as f32 + FRAME_BORDER + HEADER_HEIGHT,
+        );
+        self.fit_window(ctx, frame, layout);
+        let Some(Spectating { state, .. }) = &self.spectating else {
+            return;
+        };
+        let mut exit = false;
+        egui::TopBottomPanel::top("spectating").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Score: {}", state.score));
+                exit = ui.button("Exit").clicked();
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| self.show_game(ui, state));
+        if let Some(err) = failure {
+            self.message = Some(err.to_string());
+            self.spectating = None;
+        } else if exit {
+            self.spectating = None;
+        }
+    }
+
+    /// Start or stop broadcasting the game to spectators on the address
+    fn toggle_broadcast(&mut self) {
+        if self.broadcast.take().is_some() {
+            self.message = None;
+            return;
+        }
+        match SpectatorServer::bind(&self.address) {
+            Ok(server) => {
+                self.broadcast = Some(server);
+                self.message = Some(format!("Broadcasting on {}", self.address));
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    /// Host or join a networked versus game on a separate thread (connecting blocks)
+    fn connect(&mut self, host: bool) {
+        let address = self.address.clone();
+        let netcode = self.netcode;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let listen = || std::net::TcpListener::bind(&address).map_err(NetError::Io);
+            let game = match (host, netcode) {
+                (true, Netcode::Lockstep) => listen()
+                    .and_then(|listener| {
+                        NetVersus::host(&listener, Ruleset::default(), random_seed())
+                    })
+                    .map(|game| VersusGame::Network(Box::new(game))),
+                (true, Netcode::Rollback) => listen()
+                    .and_then(|listener| {
+                        RollbackVersus::host(&listener, Ruleset::default(), random_seed())
+                    })
+                    .map(|game| VersusGame::Rollback(Box::new(game))),
+                (false, Netcode::Lockstep) => {
+                    NetVersus::join(&address).map(|game| VersusGame::Network(Box::new(game)))
+                }
+                (false, Netcode::Rollback) => {
+                    RollbackVersus::join(&address).map(|game| VersusGame::Rollback(Box::new(game)))
+                }
+            };
+            let _ = sender.send(game);
+        });
+        self.connecting = Some(receiver);
+        self.message = Some(match host {
+            true => format!("Waiting for a player on {}", self.address),
+            false => format!("Joining {}", self.address),
+        });
+    }
+
+    /// Start the networked versus game once connected
+    fn check_connection(&mut self) {
+        let Some(result) = self
+            .connecting
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        else {
+            return;
+        };
+        self.connecting = None;
+        match result {
+            Ok(game) => {
+                // the game is stopped while the versus game is played
+                self.session.stop();
+                self.message = None;
+                // the local player uses the single player keys
+                self.versus = Some(VersusMatch::new(
+                    game,
+                    [self.bindings.clone(), Bindings::default()],
+                ));
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    /// Check once if the finished game enters the leaderboard, prompting for the player name
+    fn check_high_score(&mut self, state: &DisplayState) {
+        let seed = self.session.engine().replay().seed;
+        if self.checked_game == Some(seed) {
+            return;
+        }
+        self.checked_game = Some(seed);
+        if self
+            .high_scores
+            .qualifies(MARATHON, state.scene_area, state.score)
+        {
+            self.score_prompt = Some(HighScoreEntry {
+                name: String::new(),
+                score: state.score,
+                time: state.stats.elapsed,
+                lines: state.stats.lines(),
+                level: state.level,
+                date: chrono::Utc::now(),
+                seed,
+            });
+        }
+    }
+
+    /// Draw the windows of the leaderboard: the name prompt and the table
+    fn show_high_scores(&mut self, ctx: &Context, scene_area: Position) {
+        let mut submitted = false;
+        if let Some(entry) = &mut self.score_prompt {
+            egui::Window::new("New high score!")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!("Score: {}", entry.score));
+                    ui.text_edit_singleline(&mut entry.name).request_focus();
+                    submitted =
+                        ui.button("OK").clicked() || ui.input().key_pressed(egui::Key::Enter);
+                });
+        }
+        if submitted {
+            if let Some(mut entry) = self.score_prompt.take() {
+                if entry.name.trim().is_empty() {
+                    entry.name = "Anonymous".to_owned();
+                }
+                self.high_scores.insert(MARATHON, scene_area, entry);
+                self.message = self
+                    .high_scores
+                    .save(std::path::Path::new(HIGHSCORES_PATH))
+                    .err()
+                    .map(|err| err.to_string());
+                self.show_high_scores = true;
+            }
+        }
+
+        let entries = self.high_scores.table(MARATHON, scene_area);
+        egui::Window::new(format!(
+            "High scores - {MARATHON} {}x{}",
+            scene_area.x, scene_area.y
+        ))
+        .open(&mut self.show_high_scores)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if entries.is_empty() {
+                ui.label("No scores yet");
+            }
+            egui::Grid::new("high_scores").striped(true).show(ui, |ui| {
+                for (rank, entry) in entries.iter().enumerate() {
+                    ui.small(format!("{}.", rank + 1));
+                    ui.small(&entry.name);
+                    ui.small(entry.score.to_string());
+                    ui.small(format!("{} lines", entry.lines));
+                    ui.small(format!("level {}", entry.level));
+                    ui.small(format!("{}s", entry.time.as_secs()));
+                    ui.small(entry.date.format("%Y-%m-%d").to_string());
+                    ui.small(format!("seed {}", entry.seed));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    /// Draw the controls window: presets, keys of each action and their conflicts,
+    /// binding the key pressed to the action waiting for one
+    fn show_controls(&mut self, ctx: &Context) {
+        if let Some(action) = self.rebinding {
+            let pressed = ctx.input().events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } => Some(*key),
+                _ => None,
+            });
+            match pressed {
+                // escape cancels the binding
+                Some(egui::Key::Escape) => self.rebinding = None,
+                Some(key) => {
+                    let conflicts = self.bindings.bind(action, key);
+                    self.rebinding = None;
+                    self.message = conflicts
+                        .first()
+                        .map(|other| format!("{} is also bound to {}", key.name(), other.label()));
+                    self.save_bindings();
+                }
+                None => {}
+            }
+        }
+
+        let mut changed = false;
+        let mut animations_changed = false;
+        let mut open = self.show_controls;
+        egui::Window::new("Controls")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for preset in Preset::ALL {
+                        if ui.small_button(preset.label()).clicked() {
+                            let handling = self.bindings.handling;
+                            self.bindings = Bindings::preset(preset);
+                            self.bindings.handling = handling;
+                            changed = true;
+                        }
+                    }
+                });
+                egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.small(action.label());
+                        ui.horizontal(|ui| {
+                            // clicking a key removes it
+                            for key in self.bindings.keys(action).to_vec() {
+                                if ui.small_button(key.name()).clicked() {
+                                    self.bindings.unbind(action, key);
+                                    changed = true;
+                                }
+                            }
+                            let waiting = self.rebinding == Some(action);
+                            if ui
+                                .small_button(if waiting { "Press a key" } else { "+" })
+                                .clicked()
+                            {
+                                self.rebinding = Some(action);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                for (key, actions) in self.bindings.conflicts() {
+                    let actions: Vec<_> = actions.iter().map(|action| action.label()).collect();
+                    ui.colored_label(
+                        Color32::RED,
+                        format!("{} is bound to {}", key.name(), actions.join(", ")),
+                    );
+                }
+
+                // handling settings, applied to the next game
+                ui.separator();
+                let handling = &mut self.bindings.handling;
+                for (label, value, max) in [
+                    ("DAS (ms)", &mut handling.das, 500),
+                    ("ARR (ms)", &mut handling.arr, 200),
+                    ("DAS cut (ms)", &mut handling.dcd, 200),
+                ] {
+                    changed |= ui
+                        .add(egui::Slider::new(value, 0..=max).text(label))
+                        .changed();
+                }
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut handling.soft_drop_factor, 1..=100)
+                            .text("Soft drop factor"),
+                    )
+                    .changed();
+                ui.small("Handling applies from the next game");
+
+                // animation settings, in milliseconds
+                ui.separator();
+                let settings = &mut self.animator.settings;
+                animations_changed |= ui
+                    .checkbox(&mut settings.reduced_motion, "Reduce motion")
+                    .changed();
+                ui.add_enabled_ui(!settings.reduced_motion, |ui| {
+                    for (label, value, max) in [
+                        ("Line clear (ms)", &mut settings.line_clear, 1000),
+                        ("Lock flash (ms)", &mut settings.lock, 500),
+                        ("Hard drop trail (ms)", &mut settings.hard_drop, 500),
+                        ("Particles (ms)", &mut settings.particles, 2000),
+                        ("Shake (ms)", &mut settings.shake, 500),
+                    ] {
+                        animations_changed |= ui
+                            .add(egui::Slider::new(value, 0..=max).text(label))
+                            .changed();
+                    }
+                });
+            });
+        self.show_controls = open;
+        if !open {
+            self.rebinding = None;
+        }
+        if changed {
+            self.save_bindings();
+        }
+        if animations_changed {
+            let path = std::path::Path::new(ANIMATIONS_PATH);
+            if let Err(err) = self.animator.settings.save(path) {
+                self.message = Some(err.to_string());
+            }
+        }
+    }
+
+    /// Write the key bindings to their file, reporting the errors
+    fn save_bindings(&mut self) {
+        if let Err(err) = self.bindings.save(std::path::Path::new(BINDINGS_PATH)) {
+            self.message = Some(err.to_string());
+        }
     }
 
     /// Build and draw the right panel (stats) using the game display state
-    fn show_stats(&self, ui: &mut egui::Ui, state: &DisplayState) {
-        // Define the drawer for the next tetronimo
-        let next_tetronimo_drawer = |ui: &mut egui::Ui| {
+    fn show_stats(&mut self, ui: &mut egui::Ui, state: &DisplayState) {
+        // networked versus game to host (true) or join (false), once the panel is drawn
+        let mut connect = None;
+        // broadcast toggled or remote game to watch, once the panel is drawn
+        let mut toggle_broadcast = false;
+        let mut watch = false;
+        // theme selected, switched to once the panel is drawn
+        let mut theme = self.theme;
+        let accent = self.theme().panel.accent;
+        // Define the drawer for the next and held tetronimos
+        let piece_drawer = |ui: &mut egui::Ui, piece: &[Position], kind: Option<Kind>| {
             // allocate the painter area (4x4 blocks)
             let (response, painter) = ui.allocate_painter(
                 Vec2::splat(STATS_PANEL_WIDTH * self.block_size),
                 egui::Sense::focusable_noninteractive(),
             );
-            // add the shapes of the next tetronimo to the painter
-            painter.extend(build_blocks(
-                &state.next,
+            // add the shapes of the tetronimo to the painter
+            let mut blocks = BlockShapes::new(
                 self.block_size,
                 response.rect.left_top(),
-                Color32::GOLD,
-                Color32::BLACK,
-            ));
+                &self.themes[self.theme],
+                self.skin.as_ref(),
+            );
+            blocks.add_piece(piece, kind);
+            painter.extend(blocks.build());
             response
         };
         // Define the drawer for the entire stats panel
         let stats_panel_drawer = |ui: &mut egui::Ui| {
-            // Next tetronimo area (using the drawer defined above)
+            // Next and held tetronimo areas (using the drawer defined above)
             ui.label("Next:");
-            egui::Frame::canvas(ui.style()).show(ui, next_tetronimo_drawer);
+            egui::Frame::canvas(ui.style())
+                .show(ui, |ui| piece_drawer(ui, &state.next, state.next_kind));
+            ui.label("Hold:");
+            egui::Frame::canvas(ui.style())
+                .show(ui, |ui| piece_drawer(ui, &state.hold, state.hold_kind));
 
             // Total score area:
             ui.separator();
             ui.label(format!("Score: {:?}", state.score));
+            if let Some(announcement) = &self.announcement {
+                ui.colored_label(accent, announcement);
+            }
+
+            // Expandable detailed statistics area
+            egui::CollapsingHeader::new("Stats").show(ui, |ui| {
+                let stats = &state.stats;
+                for line in [
+                    format!("Pieces: {}", stats.pieces),
+                    format!("PPS: {:.2}", stats.pieces_per_second()),
+                    format!("KPP: {:.2}", stats.inputs_per_piece()),
+                    format!("Lines: {}", stats.lines()),
+                    format!("Singles: {}", stats.clears[0]),
+                    format!("Doubles: {}", stats.clears[1]),
+                    format!("Triples: {}", stats.clears[2]),
+                    format!("Tetrises: {}", stats.clears[3]),
+                    format!("T-spins: {}", stats.t_spins),
+                    format!("Max combo: {}", stats.max_combo),
+                    format!("Time: {}s", stats.elapsed.as_secs()),
+                ] {
+                    ui.small(line);
+                }
+                for (kind, count) in Kind::ALL.iter().zip(stats.piece_counts) {
+                    ui.small(format!("{kind:?}: {count}"));
+                }
+            });
 
             // Logo area (square with the size of the panel width)
             ui.separator();
             ui.image(&self.logo, Vec2::splat(ui.available_width()));
 
-            // Start and reset buttons
             ui.separator();
-            if ui.button("Start").clicked() {
-                // Create the thread and start running the game engine
-                GameInterface::start(Arc::clone(&self.engine), ui.ctx().clone());
-            };
-            if ui.button("Reset").clicked() {
-                // Reset the engine state
-                self.engine.lock().reset();
-            };
+            match &mut self.playback {
+                // Replay controls: play/pause, speed and exit
+                Some(player) => {
+                    if player.finished() {
+                        ui.label("Replay over");
+                    }
+                    if ui
+                        .button(if player.paused { "Play" } else { "Pause" })
+                        .clicked()
+                    {
+                        player.paused = !player.paused;
+                    };
+                    ui.horizontal(|ui| {
+                        if ui.button("-").clicked() {
+                            player.speed = (player.speed / 2.).max(0.25);
+                        };
+                        ui.label(format!("x{}", player.speed));
+                        if ui.button("+").clicked() {
+                            player.speed = (player.speed * 2.).min(8.);
+                        };
+                    });
+                    if ui.button("Exit").clicked() {
+                        self.playback = None;
+                    };
+                }
+                // Session buttons (enabled according to the session state),
+                // along with the leaderboard and file buttons
+                None => {
+                    let running = self.session.is_running();
+                    let startable = !running && !state.game_over;
+                    if ui
+                        .add_enabled(startable, egui::Button::new("Start"))
+                        .clicked()
+                    {
+                        self.session.start();
+                    };
+                    if ui.add_enabled(running, egui::Button::new("Stop")).clicked() {
+                        self.session.stop();
+                    };
+                    if ui.button("Restart").clicked() {
+                        let ruleset = self.bindings.handling.apply(Ruleset::default());
+                        self.session.restart_with(ruleset);
+                        self.announcement = None;
+                    };
+                    if ui.button("Versus").clicked() {
+                        // the game is stopped while the versus game is played
+                        self.session.stop();
+                        self.versus = Some(VersusMatch::local());
+                    };
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("bot")
+                            .selected_text(format!("{:?}", self.bot_strength))
+                            .show_ui(ui, |ui| {
+                                for strength in Strength::ALL {
+                                    let label = format!("{strength:?}");
+                                    ui.selectable_value(&mut self.bot_strength, strength, label);
+                                }
+                            });
+                        if ui.button("Versus bot").clicked() {
+                            self.session.stop();
+                            self.versus = Some(VersusMatch::against_bot(
+                                self.bot_strength,
+                                self.bindings.clone(),
+                            ));
+                        };
+                    });
+                    let demo = self.demo.is_some();
+                    if ui.button(if demo { "End demo" } else { "Demo" }).clicked() {
+                        // a new game for the player, or for the bot
+                        self.demo = match demo {
+                            true => {
+                                let ruleset = self.bindings.handling.apply(Ruleset::default());
+                                self.session.restart_with(ruleset);
+                                None
+                            }
+                            false => {
+                                self.session.restart_with(Ruleset::default());
+                                Some(Autopilot::new(self.bot_strength, random_seed()))
+                            }
+                        };
+                        self.announcement = None;
+                    };
+                    ui.text_edit_singleline(&mut self.address);
+                    ui.horizontal(|ui| {
+                        let idle = self.connecting.is_none();
+                        if ui.add_enabled(idle, egui::Button::new("Host")).clicked() {
+                            connect = Some(true);
+                        };
+                        if ui.add_enabled(idle, egui::Button::new("Join")).clicked() {
+                            connect = Some(false);
+                        };
+                    });
+                    // both players must pick the same netcode
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.netcode, Netcode::Rollback, "Rollback");
+                        ui.selectable_value(&mut self.netcode, Netcode::Lockstep, "Lockstep");
+                    });
+                    ui.horizontal(|ui| {
+                        let broadcasting = self.broadcast.is_some();
+                        toggle_broadcast = ui
+                            .button(if broadcasting {
+                                "Unbroadcast"
+                            } else {
+                                "Broadcast"
+                            })
+                            .clicked();
+                        watch = ui.button("Watch").clicked();
+                    });
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("theme")
+                            .selected_text(&self.themes[self.theme].name)
+                            .show_index(ui, &mut theme, self.themes.len(), |index| {
+                                self.themes[index].name.clone()
+                            });
+                        // write the theme in use as a starting point for a user theme
+                        if ui.small_button("Export").clicked() {
+                            self.message = Some(export_theme(&self.themes[self.theme]));
+                        }
+                    });
+                    if ui.button("Controls").clicked() {
+                        self.show_controls = !self.show_controls;
+                    };
+                    if ui.button("High scores").clicked() {
+                        self.show_high_scores = !self.show_high_scores;
+                    };
+                    if ui.button("Save game").clicked() {
+                        let path = std::path::Path::new(SAVE_PATH);
+                        self.message = self
+                            .session
+                            .engine()
+                            .save(path)
+                            .err()
+                            .map(|err| err.to_string());
+                    };
+                    if ui.button("Save replay").clicked() {
+                        let path = std::path::Path::new(REPLAY_PATH);
+                        self.message = self
+                            .session
+                            .engine()
+                            .replay()
+                            .save(path)
+                            .err()
+                            .map(|err| err.to_string());
+                    };
+                    if ui.button("Replay").clicked() {
+                        match Replay::load(std::path::Path::new(REPLAY_PATH)) {
+                            Ok(replay) => {
+                                // the game is stopped while the replay is played back
+                                self.session.stop();
+                                self.playback = Some(ReplayPlayer::new(replay));
+                            }
+                            Err(err) => self.message = Some(err.to_string()),
+                        }
+                    };
+                }
+            }
+            if let Some(message) = &self.message {
+                ui.small(message);
+            }
         };
         // Finally, draw the right-side panel (using the drawer defined above)
         egui::SidePanel::right("stats").show_inside(ui, |ui| {
             ui.set_width(STATS_PANEL_WIDTH * self.block_size);
             ui.vertical_centered(stats_panel_drawer);
         });
+        if let Some(host) = connect {
+            self.connect(host);
+        }
+        if theme != self.theme {
+            self.set_theme(ui.ctx(), theme);
+        }
+        if toggle_broadcast {
+            self.toggle_broadcast();
+        }
+        if watch {
+            // the game is stopped while the remote game is watched
+            self.session.stop();
+            self.message = None;
+            self.spectating = Some(Spectating {
+                client: SpectatorClient::connect(self.address.clone()),
+                state: DisplayState::default(),
+            });
+        }
     }
 
     /// Build and draw the central panel (main game area) using the game display state
@@ -133,11 +1118,22 @@ impl GameInterface {
                 ),
                 egui::Sense::hover(),
             );
-            // add the block shapes of the whole scence to the painter
+            // add the block shapes of the whole scence to the painter,
+            // shaken and with the animations on top
+            let reference = response.rect.left_top() + self.animator.shake() * self.block_size;
             painter.extend(build_game_blocks(
                 state,
                 self.block_size,
-                response.rect.left_top(),
+                reference,
+                self.theme(),
+                self.skin.as_ref(),
+                &self.animator.lifts(state),
+            ));
+            painter.extend(self.animator.shapes(
+                self.block_size,
+                reference,
+                state.scene_area,
+                self.theme(),
             ));
             response
         };
@@ -146,20 +1142,43 @@ impl GameInterface {
             .frame(egui::Frame::canvas(ui.style()))
             .show_inside(ui, game_scene_drawer);
     }
+}
 
-    /// Start the thread running the game engine
-    fn start(game: Arc<Mutex<Tetris>>, ctx: Context) {
-        std::thread::spawn(move || {
-            // local one-line function to get the lock on the engine and run an event
-            // this ensures the lock is freed right after each execution
-            let lock_and_run = || game.lock().run(Event::MoveDown);
+/// Draw the meter of the garbage lines waiting to be added to a player stash
+fn show_garbage_meter(ui: &mut egui::Ui, incoming: u32, scene_area: Position, block_size: f32) {
+    let (response, painter) = ui.allocate_painter(
+        vec2(GARBAGE_METER_WIDTH, scene_area.y as f32) * block_size,
+        egui::Sense::hover(),
+    );
+    let lines = incoming.min(scene_area.y as u32) as f32;
+    let rect = response.rect;
+    painter.rect_filled(
+        egui::Rect::from_min_max(
+            egui::pos2(rect.left(), rect.bottom() - lines * block_size),
+            rect.right_bottom(),
+        ),
+        egui::Rounding::none(),
+        Color32::RED,
+    );
+}
 
-            // Loop running a MoveDown event and waiting a given amount of time,
-            // while game engine active
-            while let Some(interval) = lock_and_run() {
-                ctx.request_repaint();
-                std::thread::sleep(interval);
-            }
-        });
+/// Write a theme to a file of the themes directory, returning the message to display
+fn export_theme(theme: &Theme) -> String {
+    let dir = std::path::Path::new(THEMES_DIR);
+    let file = theme.name.to_lowercase().replace(' ', "_") + ".json";
+    let path = dir.join(file);
+    let saved = std::fs::create_dir_all(dir)
+        .map_err(Into::into)
+        .and_then(|_| theme.save(&path));
+    match saved {
+        Ok(()) => format!("Theme exported to {}", path.display()),
+        Err(err) => err.to_string(),
     }
 }
+
+/// Return a seed for a new game, from the current time
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
@@ -0,0 +1,193 @@
+//! Keyboard input handler: the keys pressed and held are tracked frame by frame and turned
+//! into engine events, leaving the auto repeat (DAS/ARR) and soft drop to the engine.
+
+use crate::bindings::{Action, Bindings};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tetris_core::geometry::Direction;
+use tetris_core::ruleset::Ruleset;
+use tetris_core::tetris::Event;
+
+/// Handling settings of the player, applied to the rules of the new games
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handling {
+    /// delayed auto shift, in milliseconds
+    pub das: u64,
+    /// auto repeat rate, in milliseconds (0 moves to the wall at once)
+    pub arr: u64,
+    /// DAS cut delay after a rotation or a spawn, in milliseconds
+    pub dcd: u64,
+    pub soft_drop_factor: u32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        let ruleset = Ruleset::default();
+        Self {
+            das: ruleset.das.as_millis() as u64,
+            arr: ruleset.arr.as_millis() as u64,
+            dcd: ruleset.dcd.as_millis() as u64,
+            soft_drop_factor: ruleset.soft_drop_factor,
+        }
+    }
+}
+
+impl Handling {
+    /// Return the rules with the handling settings
+    pub fn apply(&self, ruleset: Ruleset) -> Ruleset {
+        Ruleset {
+            das: Duration::from_millis(self.das),
+            arr: Duration::from_millis(self.arr),
+            dcd: Duration::from_millis(self.dcd),
+            soft_drop_factor: self.soft_drop_factor,
+            ..ruleset
+        }
+    }
+}
+
+/// Keys of the game actions during a frame: held for the moves and the soft drop,
+/// pressed for the hold, the rotation and the hard drop
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Keys {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub hold: bool,
+    pub rotate: bool,
+    pub hard_drop: bool,
+}
+
+impl Keys {
+    /// Read the keys of the bindings in the Context
+    pub fn from_context(ctx: &egui::Context, bindings: &Bindings) -> Self {
+        Self {
+            left: bindings.down(ctx, Action::Left),
+            right: bindings.down(ctx, Action::Right),
+            soft_drop: bindings.down(ctx, Action::SoftDrop),
+            hold: bindings.pressed(ctx, Action::Hold),
+            rotate: bindings.pressed(ctx, Action::Rotate),
+            hard_drop: bindings.pressed(ctx, Action::HardDrop),
+        }
+    }
+}
+
+/// Tracker of the keys of a player
+#[derive(Debug, Default)]
+pub struct InputHandler {
+    // keys of the previous frame
+    previous: Keys,
+    // horizontal move held, if any
+    shift: Option<Direction>,
+    // horizontal direction pressed last, winning while both are held
+    last: Option<Direction>,
+}
+
+impl InputHandler {
+    /// Return the events of every action of the frame, in a defined order: hold,
+    /// rotation, then moves, then drops
+    pub fn update(&mut self, keys: Keys) -> Vec<Event> {
+        let mut events = Vec::new();
+        if keys.hold {
+            events.push(Event::Hold);
+        }
+        if keys.rotate {
+            events.push(Event::Rotate);
+        }
+        if keys.left && !self.previous.left {
+            self.last = Some(Direction::Left);
+        }
+        if keys.right && !self.previous.right {
+            self.last = Some(Direction::Right);
+        }
+        let shift = match (keys.left, keys.right) {
+            (true, true) => self.last,
+            (true, false) => Some(Direction::Left),
+            (false, true) => Some(Direction::Right),
+            (false, false) => None,
+        };
+        if shift != self.shift {
+            self.shift = shift;
+            events.push(match shift {
+                Some(Direction::Left) => Event::ShiftLeft,
+                Some(Direction::Right) => Event::ShiftRight,
+                _ => Event::ShiftStop,
+            });
+        }
+        if keys.soft_drop != self.previous.soft_drop {
+            events.push(match keys.soft_drop {
+                true => Event::SoftDrop,
+                false => Event::SoftDropStop,
+            });
+        }
+        if keys.hard_drop {
+            events.push(Event::Drop);
+        }
+        self.previous = keys;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{Handling, InputHandler, Keys};
+    use std::time::Duration;
+    use tetris_core::ruleset::Ruleset;
+    use tetris_core::tetris::Event;
+
+    #[test]
+    fn handling_test() {
+        // an ARR above the DAS delay is kept, the engine repeating the moves at its interval
+        let handling = Handling {
+            das: 0,
+            arr: 33,
+            ..Handling::default()
+        };
+        let ruleset = handling.apply(Ruleset::default());
+        assert_eq!(
+            (ruleset.das, ruleset.arr),
+            (Duration::ZERO, Duration::from_millis(33))
+        );
+    }
+
+    #[test]
+    fn keys_test() {
+        let mut input = InputHandler::default();
+        let left = Keys {
+            left: true,
+            ..Keys::default()
+        };
+        let both = Keys {
+            right: true,
+            ..left
+        };
+        assert_eq!(input.update(left), vec![Event::ShiftLeft]);
+        assert!(input.update(left).is_empty());
+
+        // the direction pressed last wins, the other one takes over once released
+        assert_eq!(input.update(both), vec![Event::ShiftRight]);
+        assert_eq!(input.update(left), vec![Event::ShiftLeft]);
+
+        let soft_drop = Keys {
+            soft_drop: true,
+            ..Keys::default()
+        };
+        assert_eq!(
+            input.update(soft_drop),
+            vec![Event::ShiftStop, Event::SoftDrop]
+        );
+        assert_eq!(input.update(Keys::default()), vec![Event::SoftDropStop]);
+
+        // every action of a frame is run, hold first and drop last
+        let finesse = Keys {
+            right: true,
+            hold: true,
+            rotate: true,
+            hard_drop: true,
+            ..Keys::default()
+        };
+        assert_eq!(
+            input.update(finesse),
+            vec![Event::Hold, Event::Rotate, Event::ShiftRight, Event::Drop]
+        );
+    }
+}
@@ -1,20 +1,34 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use gui::GameInterface;
+use window::WindowGeometry;
 
-mod geometry;
-mod blocks;
-mod tetris;
-mod tetronimo;
-mod utils;
+mod animation;
+mod bindings;
 mod gui;
+mod input;
+mod skin;
+mod theme;
+mod utils;
+mod window;
 
 fn main() {
-    // define global options
+    // define global options, restoring the window geometry of the last session if any
+    let geometry = WindowGeometry::load(std::path::Path::new(gui::WINDOW_PATH)).ok();
     let options = eframe::NativeOptions {
-        resizable: false,
+        initial_window_pos: geometry.and_then(|geometry| geometry.position),
+        // the default size fits the board and the stats panel with blocks of 25 points
+        initial_window_size: Some(
+            geometry.map_or(egui::vec2(381., 531.), |geometry| geometry.size),
+        ),
+        min_window_size: Some(egui::vec2(200., 200.)),
+        fullscreen: geometry.is_some_and(|geometry| geometry.fullscreen),
         ..eframe::NativeOptions::default()
     };
     // Start the main window thread with the Game Interface
-    eframe::run_native("Tetris", options, Box::new(|cc| Box::new(GameInterface::new(cc))));
+    eframe::run_native(
+        "Tetris",
+        options,
+        Box::new(|cc| Box::new(GameInterface::new(cc))),
+    );
 }
@@ -0,0 +1,96 @@
+//! Skins of the blocks: tiles sliced from an image atlas, drawn instead of the flat colors.
+//!
+//! The atlas has a column of tiles for each kind of tetronimo (in the order of `Kind::ALL`)
+//! and a last one for the garbage. A connected skin has 16 rows of tiles, one for each set
+//! of sides joined to the other blocks of the tetronimo (the row being the mask of
+//! `geometry::joints`), so that the blocks of a tetronimo merge; a plain skin has a single row.
+
+use crate::utils::load_image_from_path;
+use egui::{pos2, vec2, Rect, TextureHandle, TextureId, TextureOptions};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tetris_core::tetronimo::Kind;
+
+/// Number of columns of tiles in an atlas
+const ATLAS_COLUMNS: usize = Kind::ALL.len() + 1;
+/// Number of rows of tiles in the atlas of a connected skin
+const CONNECTED_ROWS: usize = 16;
+
+/// Skin of a theme, as written in its file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Skin {
+    /// image atlas of the tiles
+    pub atlas: PathBuf,
+    /// true if the atlas has a row of tiles for each set of joined sides
+    #[serde(default)]
+    pub connected: bool,
+    /// true to smooth the tiles when scaled, false to keep their pixels sharp
+    #[serde(default)]
+    pub smooth: bool,
+}
+
+/// Skin loaded in a texture, ready to be painted
+pub struct SkinTexture {
+    texture: TextureHandle,
+    connected: bool,
+}
+
+impl SkinTexture {
+    /// Load the atlas of a skin in a texture of the Context
+    pub fn load(ctx: &egui::Context, skin: &Skin) -> Result<Self, image::ImageError> {
+        let atlas = load_image_from_path(&skin.atlas)?;
+        let options = match skin.smooth {
+            true => TextureOptions::LINEAR,
+            false => TextureOptions::NEAREST,
+        };
+        Ok(Self {
+            texture: ctx.load_texture("skin", atlas, options),
+            connected: skin.connected,
+        })
+    }
+
+    /// Return the id of the texture, to paint meshes with
+    pub fn id(&self) -> TextureId {
+        self.texture.id()
+    }
+
+    /// Return the area of the atlas (in texture coordinates) of the tile of a block
+    /// of a kind of tetronimo (None for garbage), with its joined sides
+    pub fn uv(&self, kind: Option<Kind>, joints: u8) -> Rect {
+        tile_uv(kind, joints, self.connected)
+    }
+}
+
+/// Return the area of an atlas (in texture coordinates) of the tile of a block
+fn tile_uv(kind: Option<Kind>, joints: u8, connected: bool) -> Rect {
+    let column = kind
+        .and_then(|kind| Kind::ALL.iter().position(|&other| other == kind))
+        .unwrap_or(Kind::ALL.len());
+    let (row, rows) = match connected {
+        true => (joints as usize % CONNECTED_ROWS, CONNECTED_ROWS),
+        false => (0, 1),
+    };
+    let size = vec2(1. / ATLAS_COLUMNS as f32, 1. / rows as f32);
+    Rect::from_min_size(pos2(column as f32 * size.x, row as f32 * size.y), size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::skin::tile_uv;
+    use egui::{pos2, Rect};
+    use tetris_core::geometry::{JOINED_LEFT, JOINED_UP};
+    use tetris_core::tetronimo::Kind;
+
+    #[test]
+    fn tile_uv_test() {
+        // a column per kind, the garbage last
+        let uv = tile_uv(Some(Kind::S), JOINED_UP, false);
+        assert_eq!(uv, Rect::from_min_max(pos2(0.125, 0.), pos2(0.25, 1.)));
+        let uv = tile_uv(None, 0, false);
+        assert_eq!(uv, Rect::from_min_max(pos2(0.875, 0.), pos2(1., 1.)));
+
+        // a row per set of joined sides for a connected skin
+        let uv = tile_uv(Some(Kind::I), JOINED_UP | JOINED_LEFT, true);
+        assert_eq!(uv, Rect::from_min_max(pos2(0., 0.5625), pos2(0.125, 0.625)));
+    }
+}
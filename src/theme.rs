@@ -0,0 +1,313 @@
+//! Themes of the GUI: colors of the board, the tetronimos and the panels, and the fonts.
+//!
+//! Besides the built-in themes, themes are read from JSON files (colors as `[r, g, b, a]`),
+//! possibly with a skin texturing the blocks.
+
+use crate::skin::Skin;
+use egui::{Color32, FontData, FontDefinitions, FontFamily, Style, TextStyle, Visuals};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tetris_core::storage::{check_version, load_json, save_json, StorageError};
+use tetris_core::tetronimo::Kind;
+
+/// Version of the theme file format, bumped on every incompatible change
+const THEME_VERSION: u32 = 1;
+
+/// Colors of a block
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlockStyle {
+    pub fill: Color32,
+    pub stroke: Color32,
+}
+
+impl BlockStyle {
+    fn new(fill: Color32, stroke: Color32) -> Self {
+        Self { fill, stroke }
+    }
+}
+
+/// Drawing of the projection of the player tetronimo on the stash
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    /// outline of the blocks in a color
+    Outline(Color32),
+    /// blocks of the tetronimo color, with an opacity (0 to 255)
+    Ghost(u8),
+    Hidden,
+}
+
+/// Colors of the panels and the windows around the board
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PanelStyle {
+    /// true to start from the dark egui visuals, false from the light ones
+    pub dark: bool,
+    pub background: Color32,
+    pub text: Color32,
+    /// color of the highlighted texts (e.g. the announcements)
+    pub accent: Color32,
+}
+
+/// Font of the GUI and its sizes, for the default block size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontStyle {
+    /// TrueType font file (None for the bundled font)
+    pub file: Option<PathBuf>,
+    pub body: f32,
+    pub button: f32,
+}
+
+impl Default for FontStyle {
+    fn default() -> Self {
+        Self {
+            file: None,
+            body: 24.,
+            button: 20.,
+        }
+    }
+}
+
+/// Colors and fonts of the GUI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    version: u32,
+    pub name: String,
+    /// background of the board
+    pub background: Color32,
+    /// lines between the cells of the board (transparent for none)
+    pub grid: Color32,
+    /// colors of the blocks of each kind of tetronimo (garbage colors for the missing kinds)
+    pub pieces: HashMap<Kind, BlockStyle>,
+    /// colors of the garbage blocks
+    pub garbage: BlockStyle,
+    pub projection: Projection,
+    pub panel: PanelStyle,
+    #[serde(default)]
+    pub font: FontStyle,
+    /// textures of the blocks (None for the flat colors)
+    #[serde(default)]
+    pub skin: Option<Skin>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::guideline()
+    }
+}
+
+impl Theme {
+    /// Create a theme with the same colors for every kind of tetronimo
+    fn uniform(name: &str, background: Color32, blocks: BlockStyle, panel: PanelStyle) -> Self {
+        Self {
+            version: THEME_VERSION,
+            name: name.to_owned(),
+            background,
+            grid: Color32::TRANSPARENT,
+            pieces: Kind::ALL.into_iter().map(|kind| (kind, blocks)).collect(),
+            garbage: blocks,
+            projection: Projection::Ghost(64),
+            panel,
+            font: FontStyle::default(),
+            skin: None,
+        }
+    }
+
+    /// Theme with the colors of the tetronimos of the guideline
+    pub fn guideline() -> Self {
+        let stroke = Color32::from_gray(16);
+        let mut theme = Self::uniform(
+            "Guideline",
+            Color32::from_gray(16),
+            BlockStyle::new(Color32::GRAY, stroke),
+            PanelStyle {
+                dark: true,
+                background: Color32::from_gray(27),
+                text: Color32::from_gray(200),
+                accent: Color32::GOLD,
+            },
+        );
+        theme.grid = Color32::from_gray(32);
+        theme.pieces = [
+            (Kind::I, Color32::from_rgb(0, 240, 240)),
+            (Kind::O, Color32::from_rgb(240, 240, 0)),
+            (Kind::T, Color32::from_rgb(160, 0, 240)),
+            (Kind::S, Color32::from_rgb(0, 240, 0)),
+            (Kind::Z, Color32::from_rgb(240, 0, 0)),
+            (Kind::J, Color32::from_rgb(0, 0, 240)),
+            (Kind::L, Color32::from_rgb(240, 160, 0)),
+        ]
+        .into_iter()
+        .map(|(kind, fill)| (kind, BlockStyle::new(fill, stroke)))
+        .collect();
+        theme
+    }
+
+    /// Theme with white blocks on black, as on the early screens
+    pub fn monochrome() -> Self {
+        let mut theme = Self::uniform(
+            "Classic monochrome",
+            Color32::BLACK,
+            BlockStyle::new(Color32::from_gray(220), Color32::BLACK),
+            PanelStyle {
+                dark: true,
+                background: Color32::BLACK,
+                text: Color32::from_gray(220),
+                accent: Color32::WHITE,
+            },
+        );
+        theme.garbage = BlockStyle::new(Color32::from_gray(120), Color32::BLACK);
+        theme.projection = Projection::Outline(Color32::WHITE);
+        theme
+    }
+
+    /// Theme with the four shades of green of the Game Boy screen
+    pub fn game_boy() -> Self {
+        let [darkest, dark, light, lightest] = [
+            Color32::from_rgb(15, 56, 15),
+            Color32::from_rgb(48, 98, 48),
+            Color32::from_rgb(139, 172, 15),
+            Color32::from_rgb(155, 188, 15),
+        ];
+        let mut theme = Self::uniform(
+            "Game Boy",
+            lightest,
+            BlockStyle::new(dark, darkest),
+            PanelStyle {
+                dark: false,
+                background: light,
+                text: darkest,
+                accent: dark,
+            },
+        );
+        for kind in [Kind::I, Kind::O, Kind::T] {
+            theme.pieces.insert(kind, BlockStyle::new(darkest, darkest));
+        }
+        theme.garbage = BlockStyle::new(light, darkest);
+        theme.projection = Projection::Outline(dark);
+        theme
+    }
+
+    /// Return the built-in themes
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::guideline(), Self::monochrome(), Self::game_boy()]
+    }
+
+    /// Read a theme from a file
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let theme: Theme = load_json(path)?;
+        check_version(theme.version, THEME_VERSION)?;
+        Ok(theme)
+    }
+
+    /// Read the themes of the JSON files in a directory (none if it is missing),
+    /// along with the errors of the unreadable ones
+    pub fn load_dir(dir: &Path) -> (Vec<Self>, Vec<StorageError>) {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        });
+        paths.sort();
+        let mut themes = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            match Self::load(&path) {
+                Ok(theme) => themes.push(theme),
+                Err(err) => errors.push(err),
+            }
+        }
+        (themes, errors)
+    }
+
+    /// Write the theme to a file
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+
+    /// Return the colors of a block of a kind of tetronimo (None for garbage)
+    pub fn block(&self, kind: Option<Kind>) -> BlockStyle {
+        kind.and_then(|kind| self.pieces.get(&kind))
+            .copied()
+            .unwrap_or(self.garbage)
+    }
+
+    /// Return the fonts of the theme, reading its font file if any
+    pub fn fonts(&self) -> Result<FontDefinitions, StorageError> {
+        let font = match &self.font.file {
+            Some(file) => FontData::from_owned(std::fs::read(file)?),
+            None => FontData::from_static(include_bytes!("../resources/Milky_Coffee.ttf")),
+        };
+        let mut fonts = FontDefinitions::default();
+        fonts.font_data.insert("theme".to_owned(), font);
+        fonts
+            .families
+            .entry(FontFamily::Proportional)
+            .or_default()
+            .insert(0, "theme".to_owned());
+        Ok(fonts)
+    }
+
+    /// Return the egui style of the theme, with the font sizes scaled
+    /// (1 is the size matching the default block size)
+    pub fn style(&self, scale: f32) -> Style {
+        let panel = &self.panel;
+        let mut visuals = match panel.dark {
+            true => Visuals::dark(),
+            false => Visuals::light(),
+        };
+        visuals.panel_fill = panel.background;
+        visuals.window_fill = panel.background;
+        visuals.override_text_color = Some(panel.text);
+        // the canvas frames around the boards
+        visuals.extreme_bg_color = self.background;
+
+        let mut style = Style {
+            visuals,
+            ..Style::default()
+        };
+        for (text_style, font) in style.text_styles.iter_mut() {
+            let size = match text_style {
+                TextStyle::Body => self.font.body,
+                TextStyle::Button => self.font.button,
+                _ => font.size,
+            };
+            font.size = size * scale;
+        }
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::Theme;
+    use tetris_core::tetronimo::Kind;
+
+    #[test]
+    fn theme_files_test() {
+        let dir = std::env::temp_dir().join("tetris-themes-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let theme = Theme::game_boy();
+        theme.save(&dir.join("game_boy.json")).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+
+        // the broken theme is reported, the other files are ignored
+        let (themes, errors) = Theme::load_dir(&dir);
+        assert_eq!(themes, vec![theme]);
+        assert_eq!(errors.len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+
+        // the missing kinds of tetronimo use the garbage colors
+        let mut theme = Theme::guideline();
+        theme.pieces.remove(&Kind::T);
+        assert_eq!(theme.block(Some(Kind::T)), theme.garbage);
+        assert_ne!(theme.block(Some(Kind::I)), theme.garbage);
+    }
+}
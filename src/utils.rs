@@ -1,12 +1,11 @@
-use crate::{
-    geometry::Position,
-    tetris::{DisplayState, Event},
-};
+use crate::skin::SkinTexture;
+use crate::theme::{Projection, Theme};
 use eframe::epaint::RectShape;
-use egui::{
-    pos2, Color32, FontData, FontDefinitions, FontFamily, Key, Pos2, Rect, Rounding, Style,
-    TextStyle,
-};
+use egui::{pos2, vec2, Color32, Mesh, Pos2, Rect, Rounding};
+use tetris_core::geometry::joints;
+use tetris_core::storage::StorageError;
+use tetris_core::tetronimo::Kind;
+use tetris_core::{events::EngineEvent, geometry::Position, tetris::DisplayState};
 
 /// Returns the egui Rect position ready to be painted by the GUI
 fn get_rect(block_pos: Position, block_size: f32, gui_ref_pos: Pos2) -> Rect {
@@ -22,71 +21,203 @@ fn get_rect(block_pos: Position, block_size: f32, gui_ref_pos: Pos2) -> Rect {
     )
 }
 
-/// Returns an iterator over all shapes from the blocks in input, ready to be painted
-pub fn build_blocks(
-    blocks: &[Position],
+/// Builder of the shapes of blocks, in the flat colors of a theme or textured by a skin
+pub struct BlockShapes<'a> {
     block_size: f32,
     reference: Pos2,
-    fill: Color32,
-    stroke: Color32,
-) -> impl Iterator<Item = egui::Shape> + '_ {
-    blocks.iter().map(move |&block| {
-        RectShape {
-            rect: get_rect(block, block_size, reference),
-            rounding: Rounding::default(),
-            fill,
-            stroke: egui::Stroke::new(1.0, stroke),
+    theme: &'a Theme,
+    skin: Option<&'a SkinTexture>,
+    // flat blocks and outlines
+    shapes: Vec<egui::Shape>,
+    // textured blocks, painted over the flat shapes
+    mesh: Mesh,
+}
+
+impl<'a> BlockShapes<'a> {
+    pub fn new(
+        block_size: f32,
+        reference: Pos2,
+        theme: &'a Theme,
+        skin: Option<&'a SkinTexture>,
+    ) -> Self {
+        Self {
+            block_size,
+            reference,
+            theme,
+            skin,
+            shapes: Vec::new(),
+            mesh: skin.map_or_else(Mesh::default, |skin| Mesh::with_texture(skin.id())),
         }
-        .into()
-    })
+    }
+
+    /// Add a block of a kind of tetronimo (None for garbage), with its joined sides
+    /// and an opacity (255 for opaque)
+    pub fn add(&mut self, block: Position, kind: Option<Kind>, joints: u8, alpha: u8) {
+        let rect = get_rect(block, self.block_size, self.reference);
+        self.add_rect(rect, kind, joints, alpha);
+    }
+
+    /// Add a block painted in an area of the GUI
+    fn add_rect(&mut self, rect: Rect, kind: Option<Kind>, joints: u8, alpha: u8) {
+        if let Some(skin) = self.skin {
+            let tint = Color32::from_white_alpha(alpha);
+            return self
+                .mesh
+                .add_rect_with_uv(rect, skin.uv(kind, joints), tint);
+        }
+        let style = self.theme.block(kind);
+        let (fill, stroke) = match alpha {
+            255 => (style.fill, style.stroke),
+            _ => {
+                let [r, g, b, _] = style.fill.to_array();
+                let fill = Color32::from_rgba_unmultiplied(r, g, b, alpha);
+                (fill, Color32::TRANSPARENT)
+            }
+        };
+        self.shapes.push(
+            RectShape {
+                rect,
+                rounding: Rounding::default(),
+                fill,
+                stroke: egui::Stroke::new(1.0, stroke),
+            }
+            .into(),
+        );
+    }
+
+    /// Add the blocks of a tetronimo, joined to each other
+    pub fn add_piece(&mut self, blocks: &[Position], kind: Option<Kind>) {
+        for (&block, joints) in blocks.iter().zip(joints(blocks)) {
+            self.add(block, kind, joints, 255);
+        }
+    }
+
+    /// Add the projection of a tetronimo, in the style of the theme
+    pub fn add_projection(&mut self, blocks: &[Position], kind: Option<Kind>) {
+        match self.theme.projection {
+            Projection::Outline(stroke) => {
+                for &block in blocks {
+                    self.shapes.push(
+                        RectShape::stroke(
+                            get_rect(block, self.block_size, self.reference),
+                            Rounding::default(),
+                            egui::Stroke::new(1.0, stroke),
+                        )
+                        .into(),
+                    );
+                }
+            }
+            Projection::Ghost(alpha) => {
+                for (&block, joints) in blocks.iter().zip(joints(blocks)) {
+                    self.add(block, kind, joints, alpha);
+                }
+            }
+            Projection::Hidden => {}
+        }
+    }
+
+    /// Return the shapes of the blocks added, ready to be painted
+    pub fn build(mut self) -> Vec<egui::Shape> {
+        if !self.mesh.is_empty() {
+            self.shapes.push(self.mesh.into());
+        }
+        self.shapes
+    }
 }
 
-/// Returns an iterator over all shapes from the blocks of:
-/// - player tetronimo
+/// Returns the lines between the cells of the scene area (none if the color is transparent)
+fn build_grid(
+    scene_area: Position,
+    block_size: f32,
+    reference: Pos2,
+    color: Color32,
+) -> Vec<egui::Shape> {
+    if color == Color32::TRANSPARENT {
+        return Vec::new();
+    }
+    let stroke = egui::Stroke::new(1.0, color);
+    let (width, height) = (scene_area.x as f32, scene_area.y as f32);
+    let columns = (1..scene_area.x).map(|x| {
+        let x = reference.x + x as f32 * block_size;
+        egui::Shape::line_segment(
+            [
+                pos2(x, reference.y),
+                pos2(x, reference.y + height * block_size),
+            ],
+            stroke,
+        )
+    });
+    let rows = (1..scene_area.y).map(|y| {
+        let y = reference.y + y as f32 * block_size;
+        egui::Shape::line_segment(
+            [
+                pos2(reference.x, y),
+                pos2(reference.x + width * block_size, y),
+            ],
+            stroke,
+        )
+    });
+    columns.chain(rows).collect()
+}
+
+/// Returns all shapes of the scene, in the colors of the theme or textured by its skin:
+/// - grid
 /// - projection
-/// - stash of blocks in the scene
+/// - player tetronimo
+/// - stash of blocks in the scene, each one lifted by a number of rows (none if missing)
 pub fn build_game_blocks(
     state: &DisplayState,
     block_size: f32,
     reference: Pos2,
-) -> impl Iterator<Item = egui::Shape> + '_ {
-    build_blocks(
-        &state.player,
-        block_size,
-        reference,
-        Color32::KHAKI,
-        Color32::BLACK,
-    )
-    .chain(build_blocks(
-        &state.projection,
-        block_size,
-        reference,
-        Color32::TRANSPARENT,
-        Color32::WHITE,
-    ))
-    .chain(build_blocks(
-        &state.blocks,
-        block_size,
-        reference,
-        Color32::DARK_RED,
-        Color32::BLACK,
-    ))
+    theme: &Theme,
+    skin: Option<&SkinTexture>,
+    lifts: &[f32],
+) -> Vec<egui::Shape> {
+    let mut blocks = BlockShapes::new(block_size, reference, theme, skin);
+    blocks.add_projection(&state.projection, state.player_kind);
+    blocks.add_piece(&state.player, state.player_kind);
+    // the blocks of states without kinds (e.g. from older spectator servers) are garbage
+    for (index, &block) in state.blocks.iter().enumerate() {
+        let kind = state.kinds.get(index).copied().flatten();
+        let joints = state.joints.get(index).copied().unwrap_or_default();
+        let lift = lifts.get(index).copied().unwrap_or_default();
+        let rect = get_rect(block, block_size, reference).translate(vec2(0., -lift * block_size));
+        blocks.add_rect(rect, kind, joints, 255);
+    }
+    let mut shapes = build_grid(state.scene_area, block_size, reference, theme.grid);
+    shapes.extend(blocks.build());
+    shapes
 }
 
-/// Get the user input event from the Context, if any
-pub fn get_input_from_context(ctx: &egui::Context) -> Option<Event> {
-    if ctx.input().key_pressed(Key::ArrowRight) {
-        return Some(Event::MoveRight);
-    } else if ctx.input().key_pressed(Key::ArrowLeft) {
-        return Some(Event::MoveLeft);
-    } else if ctx.input().key_pressed(Key::ArrowUp) {
-        return Some(Event::Rotate);
-    } else if ctx.input().key_pressed(Key::ArrowDown) {
-        return Some(Event::MoveDown);
-    } else if ctx.input().key_pressed(Key::Space) {
-        return Some(Event::Drop);
+/// Return the text announcing a notable engine event, if any
+pub fn announce(event: &EngineEvent) -> Option<String> {
+    match event {
+        EngineEvent::LinesCleared {
+            rows,
+            t_spin,
+            combo,
+            perfect_clear,
+        } => {
+            let clear = ["Single", "Double", "Triple", "Tetris!"][rows.len().clamp(1, 4) - 1];
+            let spin = if *t_spin { "T-spin " } else { "" };
+            let combo = if *combo > 1 {
+                format!(" x{combo}")
+            } else {
+                String::new()
+            };
+            let perfect = if *perfect_clear {
+                " Perfect clear!"
+            } else {
+                ""
+            };
+            Some(format!("{spin}{clear}{combo}{perfect}"))
+        }
+        EngineEvent::LevelUp { level } => Some(format!("Level {level}")),
+        EngineEvent::GameOver { .. } => Some("Game over".to_owned()),
+        EngineEvent::PieceLocked { .. }
+        | EngineEvent::HardDropped { .. }
+        | EngineEvent::HoldUsed { .. } => None,
     }
-    None
 }
 
 /// Load an image from the specified path
@@ -99,24 +230,12 @@ pub fn load_image_from_path(path: &std::path::Path) -> Result<egui::ColorImage,
     })
 }
 
-/// Sets the font type and size for the GUI
-pub fn set_font_style(cc: &eframe::CreationContext<'_>) {
-    // Sets font type
-    let mut fonts = FontDefinitions::default();
-    fonts.font_data.insert(
-        "milky_coffee".to_owned(),
-        FontData::from_static(include_bytes!("../resources/Milky_Coffee.ttf")),
-    );
-    fonts
-        .families
-        .entry(FontFamily::Proportional)
-        .or_default()
-        .insert(0, "milky_coffee".to_owned());
-    cc.egui_ctx.set_fonts(fonts);
-
-    // Sets font sizes
-    let mut style = Style::default();
-    style.text_styles.entry(TextStyle::Body).or_default().size = 24.;
-    style.text_styles.entry(TextStyle::Button).or_default().size = 20.;
-    cc.egui_ctx.set_style(style);
+/// Sets the fonts of the theme for the GUI, falling back to the bundled font
+pub fn set_fonts(ctx: &egui::Context, theme: &Theme) -> Result<(), StorageError> {
+    let (fonts, result) = match theme.fonts() {
+        Ok(fonts) => (fonts, Ok(())),
+        Err(err) => (Theme::default().fonts()?, Err(err)),
+    };
+    ctx.set_fonts(fonts);
+    result
 }
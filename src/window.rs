@@ -0,0 +1,72 @@
+//! Window geometry, remembered between sessions.
+
+use egui::{Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tetris_core::storage::{check_version, load_json, save_json, StorageError};
+
+/// Version of the window geometry file format, bumped on every incompatible change
+const WINDOW_VERSION: u32 = 1;
+
+/// Position, size and fullscreen state of the window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    version: u32,
+    /// outer top left corner, in points (None if unknown)
+    pub position: Option<Pos2>,
+    /// inner size, in points
+    pub size: Vec2,
+    pub fullscreen: bool,
+}
+
+impl WindowGeometry {
+    /// Return the geometry of the window shown by the frame
+    pub fn of(frame: &eframe::Frame) -> Self {
+        let info = &frame.info().window_info;
+        Self {
+            version: WINDOW_VERSION,
+            position: info.position,
+            size: info.size,
+            fullscreen: info.fullscreen,
+        }
+    }
+
+    /// Read the geometry from a file
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let geometry: WindowGeometry = load_json(path)?;
+        check_version(geometry.version, WINDOW_VERSION)?;
+        Ok(geometry)
+    }
+
+    /// Write the geometry to a file
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::window::{WindowGeometry, WINDOW_VERSION};
+    use egui::{pos2, vec2};
+    use tetris_core::storage::StorageError;
+
+    #[test]
+    fn persistence_test() {
+        let path = std::env::temp_dir().join("tetris-window-test.json");
+        let mut geometry = WindowGeometry {
+            version: WINDOW_VERSION,
+            position: Some(pos2(10., 20.)),
+            size: vec2(381., 531.),
+            fullscreen: false,
+        };
+        geometry.save(&path).unwrap();
+        assert_eq!(WindowGeometry::load(&path).unwrap(), geometry);
+
+        // a file of a later version is rejected
+        geometry.version = WINDOW_VERSION + 1;
+        geometry.save(&path).unwrap();
+        let result = WindowGeometry::load(&path);
+        assert!(matches!(result, Err(StorageError::Version(_))));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,130 @@
+//! Minimal bot speaking the Tetris Bot Protocol, used to test the external bot support.
+//!
+//! It suggests every location a piece reaches by falling straight down, lowest first.
+//! Started with `silent` it never answers the suggestion requests, and with `garbage`
+//! it answers them with invalid messages.
+
+use std::io::{BufRead, Write};
+use tetris_core::tbp::{
+    BotMessage, FrontendMessage, Move, Orientation, PieceLocation, Spin, BOARD_HEIGHT, BOARD_WIDTH,
+};
+use tetris_core::tetronimo::Kind;
+
+/// Board of the bot: occupied cells, rows from the bottom
+type Board = Vec<Vec<bool>>;
+
+/// Return true if the piece fits in the board
+fn fits(board: &Board, location: &PieceLocation) -> bool {
+    location.cells().iter().all(|&(x, y)| {
+        (0..BOARD_WIDTH as i32).contains(&x)
+            && (0..BOARD_HEIGHT as i32).contains(&y)
+            && !board[y as usize][x as usize]
+    })
+}
+
+/// Return the locations of a piece falling straight down each column, lowest first
+fn suggest(board: &Board, kind: Kind) -> Vec<Move> {
+    let orientations = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+    let mut moves = Vec::new();
+    for orientation in orientations {
+        for x in 0..BOARD_WIDTH as i32 {
+            let mut location = PieceLocation {
+                kind,
+                orientation,
+                x,
+                y: BOARD_HEIGHT as i32 - 3,
+            };
+            if !fits(board, &location) {
+                continue;
+            }
+            while fits(
+                board,
+                &PieceLocation {
+                    y: location.y - 1,
+                    ..location
+                },
+            ) {
+                location.y -= 1;
+            }
+            moves.push(Move {
+                location,
+                spin: Spin::None,
+            });
+        }
+    }
+    moves.sort_by_key(|mv| mv.location.cells().iter().map(|cell| cell.1).max());
+    moves
+}
+
+/// Lock a piece in the board, removing the completed rows
+fn place(board: &mut Board, location: &PieceLocation) {
+    for (x, y) in location.cells() {
+        board[y as usize][x as usize] = true;
+    }
+    board.retain(|row| row.contains(&false));
+    board.resize(BOARD_HEIGHT, vec![false; BOARD_WIDTH]);
+}
+
+fn send(message: &BotMessage) {
+    let mut stdout = std::io::stdout();
+    let _ = writeln!(stdout, "{}", serde_json::to_string(message).unwrap());
+    let _ = stdout.flush();
+}
+
+fn main() {
+    let mode = std::env::args().nth(1).unwrap_or_default();
+    let mut board: Board = vec![vec![false; BOARD_WIDTH]; BOARD_HEIGHT];
+    let mut queue = Vec::new();
+    send(&BotMessage::Info {
+        name: "stub".to_owned(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        author: "tetris".to_owned(),
+        features: Vec::new(),
+    });
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        let Ok(message) = serde_json::from_str(&line) else {
+            send(&BotMessage::Error {
+                reason: format!("invalid message '{line}'"),
+            });
+            continue;
+        };
+        match message {
+            FrontendMessage::Rules => send(&BotMessage::Ready),
+            FrontendMessage::Start {
+                queue: pieces,
+                board: cells,
+                ..
+            } => {
+                queue = pieces;
+                board = cells
+                    .iter()
+                    .map(|row| row.iter().map(Option::is_some).collect())
+                    .collect();
+            }
+            FrontendMessage::Suggest => match (mode.as_str(), queue.first()) {
+                ("silent", _) => {}
+                ("garbage", _) => println!("{{\"type\": \"suggestion\", \"moves\": 3}}"),
+                (_, Some(&kind)) => send(&BotMessage::Suggestion {
+                    moves: suggest(&board, kind),
+                }),
+                (_, None) => send(&BotMessage::Error {
+                    reason: "empty queue".to_owned(),
+                }),
+            },
+            FrontendMessage::Play { mv } => {
+                place(&mut board, &mv.location);
+                if !queue.is_empty() {
+                    queue.remove(0);
+                }
+            }
+            FrontendMessage::NewPiece { piece } => queue.push(piece),
+            FrontendMessage::Stop => queue.clear(),
+            FrontendMessage::Quit => break,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{path::PathBuf, process::Command, process::ExitCode, time::Duration};
+use tetris_core::{
+    bot::{Bot, Strength},
+    ruleset::Ruleset,
+    storage,
+    tbp::ExternalBot,
+    tetris::Tetris,
+};
+
+mod script;
+
+const USAGE: &str = "usage: tetris-cli [--seed <n>] [--ruleset <file.json>] <script | ->
+       tetris-cli [--seed <n>] [--ruleset <file.json>] --tbp <bot command> [--pieces <n>]
+       tetris-cli [--seed <n>] [--ruleset <file.json>] --ai <easy|normal|hard> [--pieces <n>]";
+
+// time an external bot has to answer each message
+const BOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Options of the command line
+struct Options {
+    seed: u64,
+    ruleset: Option<PathBuf>,
+    input: Input,
+}
+
+/// Source of the game inputs
+enum Input {
+    // path of the input script, or "-" for the standard input
+    Script(String),
+    // command line of an external bot (Tetris Bot Protocol) and number of pieces it plays
+    Bot { command: String, pieces: u32 },
+    // strength of the built-in bot and number of pieces it plays
+    Ai { strength: Strength, pieces: u32 },
+}
+
+/// Parse the command line arguments
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut seed = 0;
+    let mut ruleset = None;
+    let mut script = None;
+    let mut bot = None;
+    let mut ai = None;
+    let mut pieces = 100;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("missing seed")?;
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed '{value}'"))?;
+            }
+            "--ruleset" => ruleset = Some(args.next().ok_or("missing ruleset file")?.into()),
+            "--tbp" => bot = Some(args.next().ok_or("missing bot command")?),
+            "--ai" => {
+                let value = args.next().ok_or("missing bot strength")?;
+                let strength = Strength::ALL
+                    .into_iter()
+                    .find(|strength| format!("{strength:?}").eq_ignore_ascii_case(&value));
+                ai = Some(strength.ok_or(format!("invalid bot strength '{value}'"))?);
+            }
+            "--pieces" => {
+                let value = args.next().ok_or("missing number of pieces")?;
+                pieces = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of pieces '{value}'"))?;
+            }
+            _ if script.is_none() => script = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'")),
+        }
+    }
+    let input = match (script, bot, ai) {
+        (Some(script), None, None) => Input::Script(script),
+        (None, Some(command), None) => Input::Bot { command, pieces },
+        (None, None, Some(strength)) => Input::Ai { strength, pieces },
+        (None, None, None) => return Err("missing input script".to_owned()),
+        _ => return Err("an input script and the bots cannot be combined".to_owned()),
+    };
+    Ok(Options {
+        seed,
+        ruleset,
+        input,
+    })
+}
+
+/// Play the game with the input of the options, then print the final board and the statistics
+fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    let ruleset: Ruleset = match &options.ruleset {
+        Some(path) => storage::load_json(path)?,
+        None => Ruleset::default(),
+    };
+    ruleset
+        .check()
+        .map_err(|reason| format!("invalid ruleset, {reason}"))?;
+    let mut engine = Tetris::with_seed(ruleset, options.seed);
+    match &options.input {
+        Input::Script(path) => {
+            let text = match path.as_str() {
+                "-" => std::io::read_to_string(std::io::stdin())?,
+                path => std::fs::read_to_string(path)?,
+            };
+            script::play(&mut engine, &script::parse(&text)?);
+        }
+        Input::Bot { command, pieces } => {
+            let mut words = command.split_whitespace();
+            let program = words.next().ok_or("empty bot command")?;
+            let mut bot = ExternalBot::spawn(Command::new(program).args(words), BOT_TIMEOUT)?;
+            bot.start(&engine)?;
+            for _ in 0..*pieces {
+                if engine.game_over() {
+                    break;
+                }
+                bot.play(&mut engine)?;
+            }
+            bot.stop()?;
+        }
+        Input::Ai { strength, pieces } => {
+            let mut bot = Bot::new(*strength, options.seed);
+            for _ in 0..*pieces {
+                // no placement left once the game is over
+                let Some(placement) = bot.plan(&engine) else {
+                    break;
+                };
+                for event in placement.events {
+                    engine.run(event);
+                }
+            }
+        }
+    }
+
+    let state = engine.get_display_state();
+    print!("{}", script::render_board(&state));
+    let summary = serde_json::json!({
+        "seed": options.seed,
+        "frames": engine.frame(),
+        "score": state.score,
+        "level": state.level,
+        "lines": state.stats.lines(),
+        "game_over": state.game_over,
+        "stats": state.stats,
+    });
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
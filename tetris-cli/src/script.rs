@@ -0,0 +1,158 @@
+//! Input scripts: one engine event per line, optionally preceded by a wait.
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! MoveLeft
+//! 250ms Rotate
+//! 1000ms
+//! Drop
+//! ```
+
+use std::time::Duration;
+use tetris_core::tetris::{DisplayState, Event, Tetris};
+
+/// Line of an input script: time to let the engine run, then the event to apply
+#[derive(Debug, PartialEq)]
+pub struct Step {
+    pub wait: Duration,
+    pub event: Option<Event>,
+}
+
+/// Error parsing an input script, with the (1-based) line where it happened
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parse the steps of an input script
+pub fn parse(text: &str) -> Result<Vec<Step>, ScriptError> {
+    let mut steps = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ScriptError {
+            line: index + 1,
+            message,
+        };
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace().peekable();
+        let mut wait = Duration::ZERO;
+        if let Some(millis) = tokens.peek().and_then(|token| token.strip_suffix("ms")) {
+            let millis = millis
+                .parse()
+                .map_err(|_| error(format!("invalid wait '{millis}ms'")))?;
+            wait = Duration::from_millis(millis);
+            tokens.next();
+        }
+        let event = match tokens.next() {
+            Some(name) => Some(
+                serde_json::from_value(serde_json::Value::String(name.to_owned()))
+                    .map_err(|_| error(format!("unknown event '{name}'")))?,
+            ),
+            None => None,
+        };
+        if let Some(token) = tokens.next() {
+            return Err(error(format!("unexpected '{token}'")));
+        }
+        if wait > Duration::ZERO || event.is_some() {
+            steps.push(Step { wait, event });
+        }
+    }
+    Ok(steps)
+}
+
+/// Play the steps of a script on a game engine
+pub fn play(engine: &mut Tetris, steps: &[Step]) {
+    for step in steps {
+        engine.tick(step.wait);
+        if let Some(event) = step.event {
+            engine.run(event);
+        }
+    }
+}
+
+/// Return the scene of a game as ASCII art: '#' for the stashed blocks, '@' for the
+/// player tetronimo and '.' for the empty cells
+pub fn render_board(state: &DisplayState) -> String {
+    let width = state.scene_area.x as usize;
+    let mut cells = vec![vec!['.'; width]; state.scene_area.y as usize];
+    let layers = [(&state.player, '@'), (&state.blocks, '#')];
+    for (blocks, symbol) in layers {
+        for block in blocks {
+            if let Some(cell) = cells
+                .get_mut(block.y as usize)
+                .and_then(|row| row.get_mut(block.x as usize))
+            {
+                *cell = symbol;
+            }
+        }
+    }
+    cells
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{parse, play, render_board, ScriptError, Step};
+    use std::time::Duration;
+    use tetris_core::{
+        ruleset::Ruleset,
+        tetris::{Event, Tetris},
+    };
+
+    #[test]
+    fn parse_test() {
+        let steps = parse("# opening\nMoveLeft\n\n250ms Rotate # spin\n1000ms\n").unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step {
+                    wait: Duration::ZERO,
+                    event: Some(Event::MoveLeft)
+                },
+                Step {
+                    wait: Duration::from_millis(250),
+                    event: Some(Event::Rotate)
+                },
+                Step {
+                    wait: Duration::from_secs(1),
+                    event: None
+                },
+            ]
+        );
+        assert_eq!(
+            parse("Drop\nJump").unwrap_err(),
+            ScriptError {
+                line: 2,
+                message: "unknown event 'Jump'".to_owned()
+            }
+        );
+        assert!(parse("Drop Drop").is_err());
+        assert!(parse("xms Drop").is_err());
+    }
+
+    #[test]
+    fn play_test() {
+        // the same script on the same seed always gives the same game
+        let steps = parse("MoveLeft\nDrop\n200ms MoveRight\nDrop\n").unwrap();
+        let mut engine = Tetris::with_seed(Ruleset::default(), 7);
+        play(&mut engine, &steps);
+        let mut other = Tetris::with_seed(Ruleset::default(), 7);
+        play(&mut other, &steps);
+        assert_eq!(engine.get_display_state(), other.get_display_state());
+
+        let board = render_board(&engine.get_display_state());
+        assert_eq!(board.lines().count(), 20);
+        assert_eq!(board.matches('#').count(), 8);
+        assert_eq!(board.matches('@').count(), 4);
+    }
+}
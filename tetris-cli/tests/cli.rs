@@ -0,0 +1,20 @@
+use std::process::Command;
+
+const CLI: &str = env!("CARGO_BIN_EXE_tetris-cli");
+
+#[test]
+fn invalid_ruleset_test() {
+    // a ruleset the engine can't play is reported, not run
+    let path = std::env::temp_dir().join("tetris-cli-ruleset-test.json");
+    std::fs::write(&path, r#"{"scene_area":{"x":0,"y":0}}"#).unwrap();
+    let output = Command::new(CLI)
+        .arg("--ruleset")
+        .arg(&path)
+        .args(["--ai", "easy"])
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(path);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: invalid ruleset"), "{stderr}");
+}
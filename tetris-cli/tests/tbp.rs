@@ -0,0 +1,43 @@
+use std::process::Command;
+use std::time::Duration;
+use tetris_core::{
+    ruleset::Ruleset,
+    tbp::{ExternalBot, TbpError},
+    tetris::Tetris,
+};
+
+const STUB_BOT: &str = env!("CARGO_BIN_EXE_tbp-stub-bot");
+
+fn spawn(mode: &str, timeout: Duration) -> Result<ExternalBot, TbpError> {
+    ExternalBot::spawn(Command::new(STUB_BOT).arg(mode), timeout)
+}
+
+#[test]
+fn stub_bot_play_test() {
+    let mut game = Tetris::with_seed(Ruleset::default(), 3);
+    let mut bot = spawn("", Duration::from_secs(5)).unwrap();
+    assert_eq!(bot.name(), "stub");
+    bot.start(&game).unwrap();
+    for _ in 0..20 {
+        bot.play(&mut game).unwrap();
+    }
+    let state = game.get_display_state();
+    assert_eq!(state.stats.pieces, 20);
+    assert!(!state.game_over);
+}
+
+#[test]
+fn stub_bot_errors_test() {
+    let mut game = Tetris::with_seed(Ruleset::default(), 3);
+
+    let mut bot = spawn("silent", Duration::from_millis(200)).unwrap();
+    bot.start(&game).unwrap();
+    assert!(matches!(bot.play(&mut game), Err(TbpError::Timeout)));
+
+    let mut bot = spawn("garbage", Duration::from_secs(5)).unwrap();
+    bot.start(&game).unwrap();
+    assert!(matches!(bot.play(&mut game), Err(TbpError::Protocol(_))));
+
+    // the game is left untouched by the failed moves
+    assert_eq!(game.get_display_state().stats.pieces, 0);
+}
@@ -0,0 +1,186 @@
+//! Stash of the blocks dropped at the bottom of the scene.
+
+use crate::{
+    geometry::{joints, Position, JOINED_DOWN, JOINED_UP},
+    tetronimo::{Kind, Tetronimo},
+};
+use serde::{Deserialize, Serialize};
+
+/// Blocks of the tetronimos dropped in the scene, stacked line by line from the bottom
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blocks {
+    // bottom line of the stash
+    start: u8,
+    // vector of LINES of dropped block stacks
+    content: Vec<Vec<u8>>,
+    // kind and joints of each block in the lines
+    #[serde(default)]
+    cells: Vec<Vec<Cell>>,
+}
+
+/// Kind of tetronimo of a dropped block (None for garbage),
+/// and its sides joined to the other blocks of its tetronimo
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct Cell {
+    kind: Option<Kind>,
+    joints: u8,
+}
+
+impl Blocks {
+    /// Create an empty stash whose bottom line is at the row `start`
+    pub fn new(start: u8) -> Self {
+        Self {
+            start,
+            content: Vec::new(),
+            cells: Vec::new(),
+        }
+    }
+
+    /// Add the blocks of tetronimo to the stash
+    pub fn add(&mut self, tetronimo: &Tetronimo) {
+        // stashes saved before the cells were tracked have none
+        self.cells.resize(self.content.len(), Vec::new());
+        let blocks: Vec<Position> = tetronimo.now().collect();
+        for (block, joints) in blocks.iter().zip(joints(&blocks)) {
+            // Insert new lines if needed
+            while self.content.len() < (self.start - block.y) as usize {
+                self.content.push(Vec::new());
+                self.cells.push(Vec::new());
+            }
+            // add the block to the corresponding line in the stack
+            let line = (self.start - block.y - 1) as usize;
+            self.content[line].push(block.x);
+            self.cells[line].resize(self.content[line].len() - 1, Cell::default());
+            self.cells[line].push(Cell {
+                kind: Some(tetronimo.kind()),
+                joints,
+            });
+        }
+    }
+
+    /// Clear completed rows, returning the rows removed (from the bottom up)
+    pub fn clear_completed_rows(&mut self, width: u8) -> Vec<u8> {
+        let rows = self
+            .content
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.len() >= width as usize)
+            .map(|(pos, _)| self.start - pos as u8 - 1)
+            .collect();
+        // the blocks next to the cleared lines are no longer joined to them
+        self.cells.resize(self.content.len(), Vec::new());
+        let full: Vec<bool> = self
+            .content
+            .iter()
+            .map(|line| line.len() >= width as usize)
+            .collect();
+        for (pos, _) in full.iter().enumerate().filter(|(_, &full)| full) {
+            if let Some(above) = self.cells.get_mut(pos + 1) {
+                above
+                    .iter_mut()
+                    .for_each(|cell| cell.joints &= !JOINED_DOWN);
+            }
+            if let Some(below) = pos.checked_sub(1).and_then(|pos| self.cells.get_mut(pos)) {
+                below.iter_mut().for_each(|cell| cell.joints &= !JOINED_UP);
+            }
+        }
+        let mut full = full.into_iter();
+        self.cells.retain(|_| full.next() == Some(false));
+        self.content.retain(|line| line.len() < width as usize);
+        rows
+    }
+
+    /// Insert rows at the bottom of the stash, full but for the hole column,
+    /// returning true if blocks were pushed out of the top of the scene
+    pub fn add_garbage(&mut self, lines: u8, hole: u8, width: u8) -> bool {
+        for _ in 0..lines {
+            self.content
+                .insert(0, (0..width).filter(|&x| x != hole).collect());
+            self.cells
+                .insert(0, vec![Cell::default(); width as usize - 1]);
+        }
+        let height = self.start as usize;
+        let overflow = self
+            .content
+            .get(height..)
+            .is_some_and(|lines| lines.iter().any(|line| !line.is_empty()));
+        self.content.truncate(height);
+        self.cells.truncate(height);
+        overflow
+    }
+
+    /// Get the vector of all the block postions in the stash
+    pub fn get_blocks(&self) -> Vec<Position> {
+        self.content
+            .iter()
+            .enumerate()
+            .flat_map(|(pos, line)| {
+                line.iter()
+                    .map(|&col| Position {
+                        x: col,
+                        y: self.start - pos as u8 - 1,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Get the kind of tetronimo of each block in the stash (None for garbage),
+    /// in the same order as `get_blocks`
+    pub fn get_kinds(&self) -> Vec<Option<Kind>> {
+        self.get_cells().map(|cell| cell.kind).collect()
+    }
+
+    /// Get the sides of each block in the stash joined to the other blocks of its tetronimo,
+    /// in the same order as `get_blocks`
+    pub fn get_joints(&self) -> Vec<u8> {
+        self.get_cells().map(|cell| cell.joints).collect()
+    }
+
+    /// Iterate over the cell of each block in the stash, in the same order as `get_blocks`
+    fn get_cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.content.iter().enumerate().flat_map(|(pos, line)| {
+            let cells = self.cells.get(pos);
+            (0..line.len()).map(move |col| {
+                cells
+                    .and_then(|cells| cells.get(col))
+                    .copied()
+                    .unwrap_or_default()
+            })
+        })
+    }
+
+    /// Return true if there is no block in the stash
+    pub fn is_empty(&self) -> bool {
+        self.content.iter().all(Vec::is_empty)
+    }
+
+    /// Return true if there is a dropped block at the position
+    pub fn is_occupied(&self, position: Position) -> bool {
+        position.y < self.start
+            && self
+                .content
+                .get((self.start - position.y - 1) as usize)
+                .is_some_and(|line| line.contains(&position.x))
+    }
+
+    /// Return the distance from the tetronimo to the top of the stash
+    pub fn distance_to(&self, tetronimo: &Tetronimo) -> u8 {
+        tetronimo
+            .now()
+            .map(|block| self.get_highest(block.x, block.y) - block.y)
+            .min()
+            .unwrap()
+    }
+
+    /// Get the highest row in a column stack, below a certain thresold
+    fn get_highest(&self, column: u8, threshold: u8) -> u8 {
+        let limit = (self.start - threshold).min(self.content.len() as u8);
+        self.start - limit
+            + self.content[..limit as usize]
+                .iter()
+                .rev()
+                .take_while(|line| !line.contains(&column))
+                .count() as u8
+    }
+}
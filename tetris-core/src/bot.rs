@@ -0,0 +1,406 @@
+//! Computer player choosing the placements of the tetronimos with a weighted heuristic.
+
+use crate::geometry::Position;
+use crate::movegen::{explore, leads_to, Placement};
+use crate::tetris::{Event, Tetris};
+use crate::versus::Versus;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Weights of the board features in the evaluation of a placement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Weights {
+    /// sum of the heights of the columns
+    pub height: f64,
+    /// empty cells below the top of their column
+    pub holes: f64,
+    /// sum of the height differences between adjacent columns
+    pub bumpiness: f64,
+    /// lines completed by the placement
+    pub lines: f64,
+    /// sum of the depths of the columns lower than both their neighbours
+    pub wells: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+            lines: 0.76,
+            wells: -0.1,
+        }
+    }
+}
+
+/// Features of a board, measured after a placement
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub height: u32,
+    pub holes: u32,
+    pub bumpiness: u32,
+    pub lines: u32,
+    pub wells: u32,
+}
+
+impl Features {
+    /// Measure the features of the stashed blocks of a scene, given the lines just completed
+    pub fn measure(scene_area: Position, blocks: &[Position], lines: u32) -> Self {
+        let width = scene_area.x as usize;
+        let mut heights = vec![0u32; width];
+        for block in blocks {
+            let height = (scene_area.y - block.y) as u32;
+            heights[block.x as usize] = heights[block.x as usize].max(height);
+        }
+        // every cell below the top of its column is either a block or a hole
+        let holes = heights.iter().sum::<u32>() - blocks.len() as u32;
+        let bumpiness = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+        let wells = (0..width)
+            .map(|x| {
+                let left = if x == 0 { u32::MAX } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(u32::MAX);
+                match left.min(right) {
+                    // a single column scene has no neighbours to compare with
+                    u32::MAX => 0,
+                    side => side.saturating_sub(heights[x]),
+                }
+            })
+            .sum();
+        Self {
+            height: heights.iter().sum(),
+            holes,
+            bumpiness,
+            lines,
+            wells,
+        }
+    }
+
+    /// Return the weighted score of the features (higher is better)
+    pub fn evaluate(&self, weights: &Weights) -> f64 {
+        weights.height * self.height as f64
+            + weights.holes * self.holes as f64
+            + weights.bumpiness * self.bumpiness as f64
+            + weights.lines * self.lines as f64
+            + weights.wells * self.wells as f64
+    }
+}
+
+/// Playing strength of the bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Strength {
+    /// considers the current tetronimo only, and picks one of its three best placements
+    Easy,
+    /// considers the current tetronimo only
+    Normal,
+    /// considers the current and the next tetronimos
+    Hard,
+}
+
+impl Strength {
+    pub const ALL: [Strength; 3] = [Strength::Easy, Strength::Normal, Strength::Hard];
+
+    /// Return the time taken to place a tetronimo when sparring
+    pub fn piece_delay(self) -> Duration {
+        match self {
+            Strength::Easy => Duration::from_millis(1200),
+            Strength::Normal => Duration::from_millis(700),
+            Strength::Hard => Duration::from_millis(400),
+        }
+    }
+}
+
+/// Computer player
+pub struct Bot {
+    pub weights: Weights,
+    pub strength: Strength,
+    // random number generator of the Easy strength mistakes
+    rng: ChaCha12Rng,
+}
+
+impl Bot {
+    /// Create a bot with the default weights (the seed makes its mistakes reproducible)
+    pub fn new(strength: Strength, seed: u64) -> Self {
+        Self {
+            weights: Weights::default(),
+            strength,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Return the placement chosen for the player tetronimo of the game, with the events
+    /// leading to it (None once the game is over, with no tetronimo to place)
+    pub fn plan(&mut self, game: &Tetris) -> Option<Placement> {
+        let mut scored: Vec<_> = explore(game)
+            .into_iter()
+            .map(|(placement, locked)| {
+                let score = match self.strength {
+                    Strength::Hard => explore(&locked)
+                        .iter()
+                        .map(|(_, next)| self.score(game, next))
+                        .fold(f64::NEG_INFINITY, f64::max),
+                    _ => self.score(game, &locked),
+                };
+                (score, placement)
+            })
+            .collect();
+        if scored.is_empty() {
+            return None;
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let choice = match self.strength {
+            Strength::Easy => self.rng.gen_range(0..scored.len().min(3)),
+            _ => 0,
+        };
+        Some(scored.swap_remove(choice).1)
+    }
+
+    /// Evaluate the game reached from another one
+    fn score(&self, from: &Tetris, to: &Tetris) -> f64 {
+        if to.game_over() {
+            return f64::NEG_INFINITY;
+        }
+        let state = to.get_display_state();
+        let lines = state.stats.lines() - from.get_display_state().stats.lines();
+        Features::measure(state.scene_area, &state.blocks, lines).evaluate(&self.weights)
+    }
+}
+
+/// Bot placing the tetronimos of a game at the pace of its strength, planning on a separate
+/// thread so that the game keeps running while it thinks
+pub struct Autopilot {
+    strength: Strength,
+    // games sent to the planning thread, and the placements it chose for them
+    requests: Sender<Tetris>,
+    plans: Receiver<Option<Placement>>,
+    // true while the planning thread is busy
+    planning: bool,
+    // placement planned for the player tetronimo, once ready
+    plan: Option<Placement>,
+    // time since the last placement
+    timer: Duration,
+}
+
+impl Autopilot {
+    /// Create an autopilot, starting its planning thread
+    pub fn new(strength: Strength, seed: u64) -> Self {
+        let (requests, games) = channel::<Tetris>();
+        let (sender, plans) = channel();
+        let mut bot = Bot::new(strength, seed);
+        std::thread::spawn(move || {
+            for game in games {
+                if sender.send(bot.plan(&game)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            strength,
+            requests,
+            plans,
+            planning: false,
+            plan: None,
+            timer: Duration::ZERO,
+        }
+    }
+
+    /// Return the strength of the bot
+    pub fn strength(&self) -> Strength {
+        self.strength
+    }
+
+    /// Return true while a placement is being planned
+    pub fn is_planning(&self) -> bool {
+        self.planning
+    }
+
+    /// Return the events placing the player tetronimo of the game, once the delay of the
+    /// strength elapsed and the placement is planned (none in the meantime)
+    pub fn next_events(&mut self, game: &Tetris, elapsed: Duration) -> Vec<Event> {
+        self.timer += elapsed;
+        if let Ok(plan) = self.plans.try_recv() {
+            self.planning = false;
+            self.plan = plan;
+        }
+        if game.game_over() || self.planning {
+            return Vec::new();
+        }
+        let Some(placement) = self.plan.take() else {
+            self.request(game);
+            return Vec::new();
+        };
+        if self.timer < self.strength.piece_delay() {
+            self.plan = Some(placement);
+            return Vec::new();
+        }
+        // the tetronimo may have fallen or been lifted by garbage while planning
+        if !leads_to(game, &placement) {
+            self.request(game);
+            return Vec::new();
+        }
+        self.timer = Duration::ZERO;
+        placement.events
+    }
+
+    /// Plan the placement of the player tetronimo of the game on the planning thread
+    fn request(&mut self, game: &Tetris) {
+        self.planning = self.requests.send(game.fork()).is_ok();
+    }
+}
+
+/// Bot playing a side of a versus game, placing a tetronimo at the pace of its strength
+pub struct Sparring {
+    autopilot: Autopilot,
+    // side of the versus game played
+    player: usize,
+}
+
+impl Sparring {
+    /// Create a sparring partner playing a side of a versus game
+    pub fn new(strength: Strength, seed: u64, player: usize) -> Self {
+        Self {
+            autopilot: Autopilot::new(strength, seed),
+            player,
+        }
+    }
+
+    /// Return the strength of the bot
+    pub fn strength(&self) -> Strength {
+        self.autopilot.strength()
+    }
+
+    /// Return the side of the versus game played
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Return true while a placement is being planned
+    pub fn is_planning(&self) -> bool {
+        self.autopilot.is_planning()
+    }
+
+    /// Place the next tetronimo once planned and its delay elapsed
+    /// (before the versus game is advanced)
+    pub fn tick(&mut self, versus: &mut Versus, elapsed: Duration) {
+        let events = self
+            .autopilot
+            .next_events(versus.engine(self.player), elapsed);
+        if versus.is_over() {
+            return;
+        }
+        for event in events {
+            versus.run(self.player, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bot::{Autopilot, Bot, Features, Sparring, Strength},
+        geometry::Position,
+        ruleset::Ruleset,
+        tetris::{Event, Tetris},
+        versus::Versus,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn features_test() {
+        // column heights 2, 0, 3, 1 with a hole under the top of the third column
+        let blocks = [
+            Position::new(0, 4),
+            Position::new(0, 5),
+            Position::new(2, 3),
+            Position::new(2, 5),
+            Position::new(3, 5),
+        ];
+        let features = Features::measure(Position::new(4, 6), &blocks, 1);
+        assert_eq!(
+            features,
+            Features {
+                height: 6,
+                holes: 1,
+                bumpiness: 2 + 3 + 2,
+                lines: 1,
+                wells: 2 + 2,
+            }
+        );
+    }
+
+    #[test]
+    fn bot_play_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 11);
+        let mut bot = Bot::new(Strength::Normal, 0);
+        for _ in 0..60 {
+            for event in bot.plan(&game).unwrap().events {
+                game.run(event);
+            }
+        }
+        let state = game.get_display_state();
+        assert!(!state.game_over);
+        assert_eq!(state.stats.pieces, 60);
+        assert!(state.stats.lines() >= 15);
+
+        // nothing left to place once the game is over
+        while !game.game_over() {
+            game.run(Event::Drop);
+        }
+        for strength in Strength::ALL {
+            assert_eq!(Bot::new(strength, 0).plan(&game), None);
+        }
+    }
+
+    #[test]
+    fn autopilot_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 11);
+        let mut autopilot = Autopilot::new(Strength::Normal, 0);
+        let delay = Strength::Normal.piece_delay();
+        // wait for the placement planned by the thread, the game going on meanwhile
+        let wait = |autopilot: &mut Autopilot, game: &Tetris| loop {
+            let events = autopilot.next_events(game, Duration::ZERO);
+            if !autopilot.is_planning() {
+                return events;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert!(autopilot.next_events(&game, delay).is_empty());
+        assert!(autopilot.is_planning());
+        let events = wait(&mut autopilot, &game);
+        assert_eq!(events.last(), Some(&Event::Drop));
+
+        // the plan of a tetronimo placed meanwhile is dropped, and the next one planned
+        let mut autopilot = Autopilot::new(Strength::Normal, 0);
+        autopilot.next_events(&game, Duration::ZERO);
+        assert!(wait(&mut autopilot, &game).is_empty());
+        game.run(Event::Drop);
+        assert!(autopilot.next_events(&game, delay).is_empty());
+        assert!(autopilot.is_planning());
+        for event in wait(&mut autopilot, &game) {
+            game.run(event);
+        }
+        assert_eq!(game.get_display_state().stats.pieces, 2);
+    }
+
+    #[test]
+    fn sparring_test() {
+        let mut versus = Versus::new(Ruleset::default(), 3);
+        let mut sparring = Sparring::new(Strength::Normal, 0, 1);
+        for _ in 0..50 {
+            sparring.tick(&mut versus, Duration::from_millis(100));
+            versus.tick(Duration::from_millis(100));
+            // wait for the placements planned, as if they took no time
+            while sparring.is_planning() {
+                std::thread::sleep(Duration::from_millis(1));
+                sparring.tick(&mut versus, Duration::ZERO);
+            }
+        }
+        // a placement every 700ms, the idle player only locking the tetronimos falling by gravity
+        let pieces = |player| versus.engine(player).get_display_state().stats.pieces;
+        assert_eq!(pieces(1), 5000 / 700);
+        assert!(pieces(0) < pieces(1));
+    }
+}
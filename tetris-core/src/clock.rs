@@ -0,0 +1,38 @@
+//! Sources of time driving the game engine.
+
+use std::time::{Duration, Instant};
+
+/// Source of time driving the game engine
+pub trait Clock {
+    /// Return the time elapsed since the previous call
+    fn elapsed(&mut self) -> Duration;
+}
+
+/// Clock following the system (wall clock) time
+pub struct SystemClock {
+    last: Instant,
+}
+
+impl SystemClock {
+    /// Create a clock counting from the current instant
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        elapsed
+    }
+}
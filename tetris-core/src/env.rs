@@ -0,0 +1,371 @@
+//! Reinforcement learning environment around the game engine.
+
+use crate::bot::Features;
+use crate::geometry::Position;
+use crate::movegen::{self, Placement};
+use crate::ruleset::Ruleset;
+use crate::tetris::{Event, Tetris, TIMESTEP};
+use crate::tetronimo::Kind;
+
+/// Kind of actions taken by the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionSpace {
+    /// one player input (or none) per frame
+    Input,
+    /// one placement of the player tetronimo per step
+    Placement,
+}
+
+/// Action taken by the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// run a player input, then advance the game by one frame (Input space)
+    Input(Event),
+    /// advance the game by one frame (Input space)
+    Wait,
+    /// lock the player tetronimo at one of the placements of the observation (Placement space)
+    Place(usize),
+}
+
+/// Rewards given to the agent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardShaping {
+    /// reward for clearing one, two, three or four lines at once
+    pub lines: [f64; 4],
+    /// reward for each locked tetronimo
+    pub piece: f64,
+    /// reward when the game is lost
+    pub game_over: f64,
+    /// reward per hole added to the stash (usually negative)
+    pub holes: f64,
+    /// reward per row added to the aggregate height of the stash (usually negative)
+    pub height: f64,
+    /// reward for an action not belonging to the action space or out of range
+    pub invalid_action: f64,
+}
+
+impl Default for RewardShaping {
+    fn default() -> Self {
+        Self {
+            lines: [1.0, 3.0, 5.0, 8.0],
+            piece: 0.01,
+            game_over: -1.0,
+            holes: 0.0,
+            height: 0.0,
+            invalid_action: -0.1,
+        }
+    }
+}
+
+/// Configuration of an environment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvConfig {
+    pub ruleset: Ruleset,
+    pub action_space: ActionSpace,
+    pub reward: RewardShaping,
+    /// number of steps after which an episode is truncated
+    pub max_steps: Option<u32>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            ruleset: Ruleset::default(),
+            action_space: ActionSpace::Placement,
+            reward: RewardShaping::default(),
+            max_steps: None,
+        }
+    }
+}
+
+/// What the agent sees of the game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// occupancy of the stash of dropped blocks, rows from the top of the scene
+    pub board: Vec<Vec<bool>>,
+    /// occupancy of the player tetronimo, rows from the top of the scene
+    pub player: Vec<Vec<bool>>,
+    pub current: Kind,
+    pub next: Kind,
+    pub hold: Option<Kind>,
+    /// placements available to the Place action (Placement space only)
+    pub placements: Vec<Placement>,
+}
+
+/// Details of a step, beyond the reward
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Info {
+    pub score: u64,
+    pub lines: u32,
+    pub pieces: u32,
+    pub frame: u64,
+    /// lines cleared during the step
+    pub cleared: u32,
+    /// true if the action was invalid (and ignored)
+    pub invalid_action: bool,
+    /// true if the episode ended by reaching the maximum number of steps
+    pub truncated: bool,
+}
+
+/// Result of a step of the environment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// Environment running one game for an agent
+pub struct Env {
+    config: EnvConfig,
+    game: Tetris,
+    // steps taken since the last reset
+    steps: u32,
+}
+
+impl Env {
+    /// Create an environment (reset it to start an episode)
+    pub fn new(config: EnvConfig) -> Self {
+        Self {
+            config,
+            game: Tetris::with_seed(config.ruleset, 0),
+            steps: 0,
+        }
+    }
+
+    /// Return the game of the environment
+    pub fn game(&self) -> &Tetris {
+        &self.game
+    }
+
+    /// Start a new episode, returning its first observation
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Tetris::with_seed(self.config.ruleset, seed);
+        self.steps = 0;
+        self.observe()
+    }
+
+    /// Take an action, returning the new observation, the reward and whether the episode is done
+    pub fn step(&mut self, action: Action) -> Step {
+        let reward = &self.config.reward;
+        let before = self.game.get_display_state();
+        let before_features = Features::measure(before.scene_area, &before.blocks, 0);
+        let valid = match (self.config.action_space, action) {
+            _ if self.game.game_over() => false,
+            (ActionSpace::Input, Action::Input(event)) => {
+                self.game.run(event);
+                self.game.tick(TIMESTEP);
+                true
+            }
+            (ActionSpace::Input, Action::Wait) => {
+                self.game.tick(TIMESTEP);
+                true
+            }
+            (ActionSpace::Placement, Action::Place(index)) => {
+                match movegen::placements(&self.game).get(index) {
+                    Some(placement) => {
+                        for &event in &placement.events {
+                            self.game.run(event);
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+        self.steps += 1;
+
+        let after = self.game.get_display_state();
+        let after_features = Features::measure(after.scene_area, &after.blocks, 0);
+        let mut value = match valid {
+            true => 0.0,
+            false => reward.invalid_action,
+        };
+        for (clears, (&was, &weight)) in after
+            .stats
+            .clears
+            .iter()
+            .zip(before.stats.clears.iter().zip(&reward.lines))
+        {
+            value += (clears - was) as f64 * weight;
+        }
+        value += (after.stats.pieces - before.stats.pieces) as f64 * reward.piece;
+        value += (after_features.holes as f64 - before_features.holes as f64) * reward.holes;
+        value += (after_features.height as f64 - before_features.height as f64) * reward.height;
+        if after.game_over && !before.game_over {
+            value += reward.game_over;
+        }
+        let truncated = self.config.max_steps.is_some_and(|max| self.steps >= max);
+
+        Step {
+            observation: self.observe(),
+            reward: value,
+            done: after.game_over || truncated,
+            info: Info {
+                score: after.score,
+                lines: after.stats.lines(),
+                pieces: after.stats.pieces,
+                frame: self.game.frame(),
+                cleared: after.stats.lines() - before.stats.lines(),
+                invalid_action: !valid,
+                truncated,
+            },
+        }
+    }
+
+    /// Return the observation of the current game
+    pub fn observe(&self) -> Observation {
+        let state = self.game.get_display_state();
+        let area = state.scene_area;
+        let grid = |blocks: &[Position]| {
+            let mut grid = vec![vec![false; area.x as usize]; area.y as usize];
+            for block in blocks {
+                grid[block.y as usize][block.x as usize] = true;
+            }
+            grid
+        };
+        Observation {
+            board: grid(&state.blocks),
+            player: grid(&state.player),
+            current: self.game.player().0.kind(),
+            next: self.game.next_kind(),
+            hold: self.game.hold_kind(),
+            placements: match self.config.action_space {
+                ActionSpace::Placement => movegen::placements(&self.game),
+                ActionSpace::Input => Vec::new(),
+            },
+        }
+    }
+}
+
+/// Batch of environments stepped together, in parallel threads
+pub struct VecEnv {
+    envs: Vec<Env>,
+    threads: usize,
+}
+
+impl VecEnv {
+    /// Create a batch of environments sharing the same configuration
+    pub fn new(config: EnvConfig, count: usize) -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        Self {
+            envs: (0..count).map(|_| Env::new(config)).collect(),
+            threads,
+        }
+    }
+
+    /// Return the environments of the batch
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    /// Start a new episode in every environment, one seed each
+    pub fn reset(&mut self, seeds: &[u64]) -> Vec<Observation> {
+        assert_eq!(seeds.len(), self.envs.len(), "one seed per environment");
+        self.envs
+            .iter_mut()
+            .zip(seeds)
+            .map(|(env, &seed)| env.reset(seed))
+            .collect()
+    }
+
+    /// Start a new episode in one environment (for instance once it is done)
+    pub fn reset_env(&mut self, index: usize, seed: u64) -> Observation {
+        self.envs[index].reset(seed)
+    }
+
+    /// Take one action in every environment, in parallel
+    pub fn step(&mut self, actions: &[Action]) -> Vec<Step> {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+        let chunk = self.envs.len().div_ceil(self.threads).max(1);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk)
+                .zip(actions.chunks(chunk))
+                .map(|(envs, actions)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .zip(actions)
+                            .map(|(env, &action)| env.step(action))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        env::{Action, ActionSpace, Env, EnvConfig, VecEnv},
+        tetris::Event,
+    };
+
+    #[test]
+    fn env_test() {
+        let mut env = Env::new(EnvConfig::default());
+        let observation = env.reset(1);
+        assert!(observation.board.iter().flatten().all(|&cell| !cell));
+        assert!(!observation.placements.is_empty());
+
+        // placements lock a tetronimo per step, until the game is lost
+        let mut steps = 0;
+        loop {
+            let step = env.step(Action::Place(0));
+            steps += 1;
+            assert!(!step.info.invalid_action);
+            assert_eq!(step.info.pieces, steps);
+            if step.done {
+                assert!(step.reward < 0.0);
+                break;
+            }
+            assert!(step.reward > 0.0);
+        }
+        assert!(env.game().game_over());
+
+        // inputs belong to the other action space
+        env.reset(1);
+        let step = env.step(Action::Input(Event::Drop));
+        assert!(step.info.invalid_action);
+        assert_eq!(step.info.pieces, 0);
+
+        let mut env = Env::new(EnvConfig {
+            action_space: ActionSpace::Input,
+            max_steps: Some(3),
+            ..EnvConfig::default()
+        });
+        env.reset(1);
+        assert!(!env.step(Action::Wait).done);
+        // the held tetronimo is observed
+        let step = env.step(Action::Input(Event::Hold));
+        assert_eq!(step.observation.hold, Some(observation.current));
+        assert!(!step.done);
+        let step = env.step(Action::Input(Event::Drop));
+        assert_eq!((step.info.pieces, step.info.frame), (1, 3));
+        assert!(step.done && step.info.truncated);
+    }
+
+    #[test]
+    fn vec_env_test() {
+        let seeds = [3, 4, 5, 6, 7];
+        let mut batch = VecEnv::new(EnvConfig::default(), seeds.len());
+        let observations = batch.reset(&seeds);
+        let actions: Vec<_> = (0..seeds.len()).map(Action::Place).collect();
+        let steps = batch.step(&actions);
+
+        // each environment of the batch matches a single one with the same seed and action
+        for (index, &seed) in seeds.iter().enumerate() {
+            let mut env = Env::new(EnvConfig::default());
+            assert_eq!(env.reset(seed), observations[index]);
+            assert_eq!(env.step(actions[index]), steps[index]);
+        }
+    }
+}
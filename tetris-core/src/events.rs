@@ -0,0 +1,69 @@
+//! Notifications fired by the game engine to its subscribers.
+
+use crate::geometry::Position;
+use crate::tetronimo::Kind;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Notification fired by the game engine when something happens in the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    // player tetronimo added to the stash of dropped blocks
+    PieceLocked {
+        kind: Kind,
+        blocks: Vec<Position>,
+        t_spin: bool,
+    },
+    // player tetronimo hard dropped from its blocks before the drop, by a number of rows
+    // (followed by its lock)
+    HardDropped {
+        kind: Kind,
+        blocks: Vec<Position>,
+        rows: u8,
+    },
+    // completed rows removed from the stash (rows numbered as before the removal),
+    // a perfect clear leaving it empty
+    LinesCleared {
+        rows: Vec<u8>,
+        t_spin: bool,
+        combo: u32,
+        perfect_clear: bool,
+    },
+    // player tetronimo put in the hold, replaced by the one held before or the next one
+    HoldUsed {
+        kind: Kind,
+    },
+    LevelUp {
+        level: u32,
+    },
+    GameOver {
+        score: u64,
+    },
+}
+
+/// Subscribers of the engine events, each one listening on its own channel
+#[derive(Debug, Default)]
+pub struct Observers {
+    senders: Vec<Sender<EngineEvent>>,
+}
+
+impl Observers {
+    /// Add a subscriber, returning the channel receiving the events
+    pub fn subscribe(&mut self) -> Receiver<EngineEvent> {
+        let (sender, receiver) = channel();
+        self.senders.push(sender);
+        receiver
+    }
+
+    /// Send an event to all the subscribers, forgetting the ones no longer listening
+    pub fn notify(&mut self, event: EngineEvent) {
+        self.senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+/// A copy of the engine starts without subscribers, so they are not notified twice
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,94 @@
+//! Positions and directions in the game scene.
+
+use serde::{Deserialize, Serialize};
+
+/// Movement of a tetronimo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+    Rotate,
+}
+
+/// Position of a block in the scene (origin at the top left, y pointing down)
+#[derive(Default, PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub x: u8,
+    pub y: u8,
+}
+
+impl Position {
+    /// Create a position from its coordinates
+    pub const fn new(x: u8, y: u8) -> Self {
+        Self { x, y }
+    }
+
+    /// Return the position in the middle of the top row of an area (self being its size)
+    pub fn top_middle(&self) -> Position {
+        Position {
+            x: self.x / 2,
+            y: 0,
+        }
+    }
+}
+
+/// Sides of a block joined to a neighbour block, as bits of a mask
+pub const JOINED_UP: u8 = 1;
+pub const JOINED_RIGHT: u8 = 2;
+pub const JOINED_DOWN: u8 = 4;
+pub const JOINED_LEFT: u8 = 8;
+
+/// Return the sides of each block joined to another block of the list
+pub fn joints(blocks: &[Position]) -> Vec<u8> {
+    blocks
+        .iter()
+        .map(|block| {
+            blocks.iter().fold(0, |joints, other| {
+                let offset = (
+                    other.x as i16 - block.x as i16,
+                    other.y as i16 - block.y as i16,
+                );
+                joints
+                    | match offset {
+                        (0, -1) => JOINED_UP,
+                        (1, 0) => JOINED_RIGHT,
+                        (0, 1) => JOINED_DOWN,
+                        (-1, 0) => JOINED_LEFT,
+                        _ => 0,
+                    }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::*;
+
+    #[test]
+    fn position_test() {
+        {
+            assert_eq!(Position::new(4, 4).top_middle(), Position::new(2, 0));
+            assert_eq!(Position::new(0, 4).top_middle(), Position::new(0, 0));
+            assert_eq!(Position::new(0, 0).top_middle(), Position::new(0, 0));
+            assert_eq!(Position::new(11, 10).top_middle(), Position::new(5, 0));
+        }
+    }
+
+    #[test]
+    fn joints_test() {
+        // an L tetronimo: three blocks in a column and one on the right of the bottom one
+        let blocks = [(0, 0), (0, 1), (0, 2), (1, 2)].map(|(x, y)| Position::new(x, y));
+        assert_eq!(
+            joints(&blocks),
+            vec![
+                JOINED_DOWN,
+                JOINED_UP | JOINED_DOWN,
+                JOINED_UP | JOINED_RIGHT,
+                JOINED_LEFT
+            ]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Local leaderboard, persisted to a file.
+
+use crate::geometry::Position;
+use crate::storage::{check_version, load_json, save_json, StorageError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Version of the high-score file format, bumped on every incompatible change
+const HIGHSCORES_VERSION: u32 = 1;
+
+/// Maximum number of entries kept in each table
+pub const TABLE_SIZE: usize = 10;
+
+/// Name of the (single) game mode available
+pub const MARATHON: &str = "Marathon";
+
+/// Result of a game in the leaderboard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: u64,
+    /// duration of the game
+    pub time: Duration,
+    pub lines: u32,
+    pub level: u32,
+    pub date: DateTime<Utc>,
+    /// seed of the game, to find/reproduce its replay
+    pub seed: u64,
+}
+
+/// Best results of a game mode, played with a given ruleset (size of the scene area)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScoreTable {
+    pub mode: String,
+    pub scene_area: Position,
+    /// entries sorted from the best to the worst
+    pub entries: Vec<HighScoreEntry>,
+}
+
+/// Local leaderboard: one table per game mode and ruleset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScores {
+    version: u32,
+    tables: Vec<HighScoreTable>,
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self {
+            version: HIGHSCORES_VERSION,
+            tables: Vec::new(),
+        }
+    }
+}
+
+impl HighScores {
+    /// Read the high scores from a file
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let scores: HighScores = load_json(path)?;
+        check_version(scores.version, HIGHSCORES_VERSION)?;
+        Ok(scores)
+    }
+
+    /// Read the high scores from a file, starting empty tables if it is missing or unreadable.
+    /// An unreadable file is moved aside (with a `.bak` extension) and the error is returned
+    pub fn load_or_default(path: &Path) -> (Self, Option<StorageError>) {
+        match Self::load(path) {
+            Ok(scores) => (scores, None),
+            Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                (Self::default(), None)
+            }
+            Err(err) => {
+                let _ = std::fs::rename(path, path.with_extension("bak"));
+                (Self::default(), Some(err))
+            }
+        }
+    }
+
+    /// Write the high scores to a file
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+
+    /// Return the entries of the table of a game mode and ruleset (empty if none)
+    pub fn table(&self, mode: &str, scene_area: Position) -> &[HighScoreEntry] {
+        self.tables
+            .iter()
+            .find(|table| table.mode == mode && table.scene_area == scene_area)
+            .map_or(&[], |table| &table.entries)
+    }
+
+    /// Return true if a score would enter the table of a game mode and ruleset
+    pub fn qualifies(&self, mode: &str, scene_area: Position, score: u64) -> bool {
+        let entries = self.table(mode, scene_area);
+        score > 0 && (entries.len() < TABLE_SIZE || entries.iter().any(|entry| score > entry.score))
+    }
+
+    /// Insert an entry in the table of a game mode and ruleset,
+    /// returning its rank (starting at 0) or None if it does not qualify
+    pub fn insert(
+        &mut self,
+        mode: &str,
+        scene_area: Position,
+        entry: HighScoreEntry,
+    ) -> Option<usize> {
+        if !self.qualifies(mode, scene_area, entry.score) {
+            return None;
+        }
+        let index = match self
+            .tables
+            .iter()
+            .position(|table| table.mode == mode && table.scene_area == scene_area)
+        {
+            Some(index) => index,
+            None => {
+                self.tables.push(HighScoreTable {
+                    mode: mode.to_owned(),
+                    scene_area,
+                    entries: Vec::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+        let entries = &mut self.tables[index].entries;
+        // equal scores keep the older entry first
+        let rank = entries.partition_point(|other| other.score >= entry.score);
+        entries.insert(rank, entry);
+        entries.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::Position,
+        highscores::{HighScoreEntry, HighScores, MARATHON, TABLE_SIZE},
+        storage::StorageError,
+    };
+    use std::time::Duration;
+
+    fn entry(score: u64) -> HighScoreEntry {
+        HighScoreEntry {
+            name: format!("player {score}"),
+            score,
+            time: Duration::from_secs(60),
+            lines: 0,
+            level: 0,
+            date: chrono::Utc::now(),
+            seed: score,
+        }
+    }
+
+    #[test]
+    fn insert_test() {
+        let area = Position::new(10, 20);
+        let mut scores = HighScores::default();
+        for score in 1..=TABLE_SIZE as u64 {
+            assert_eq!(scores.insert(MARATHON, area, entry(score * 10)), Some(0));
+        }
+        assert!(!scores.qualifies(MARATHON, area, 10));
+        assert_eq!(scores.insert(MARATHON, area, entry(5)), None);
+        assert_eq!(scores.insert(MARATHON, area, entry(55)), Some(5));
+
+        let table = scores.table(MARATHON, area);
+        assert_eq!(table.len(), TABLE_SIZE);
+        assert_eq!(table.first().unwrap().score, 100);
+        assert_eq!(table.last().unwrap().score, 20);
+        // tables are separated by ruleset
+        assert!(scores.table(MARATHON, Position::new(8, 16)).is_empty());
+    }
+
+    #[test]
+    fn corrupted_file_test() {
+        let path = std::env::temp_dir().join("tetris_highscores_test.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        let (scores, err) = HighScores::load_or_default(&path);
+        assert_eq!(scores, HighScores::default());
+        assert!(matches!(err, Some(StorageError::Format(_))));
+        assert!(!path.exists());
+        std::fs::remove_file(path.with_extension("bak")).unwrap();
+
+        std::fs::write(&path, r#"{"version": 99, "tables": []}"#).unwrap();
+        let (_, err) = HighScores::load_or_default(&path);
+        assert!(matches!(err, Some(StorageError::Version(99))));
+        std::fs::remove_file(path.with_extension("bak")).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+//! Headless Tetris game engine.
+//!
+//! The [`tetris::Tetris`] engine runs the player [`tetris::Event`]s and advances the game
+//! by fixed time steps, returning a [`tetris::DisplayState`] snapshot for any frontend to
+//! draw. Around it, this crate provides the game [`session`] controller, the engine
+//! [`events`] subscriptions, [`replay`] recording and playback, [`statistics`], the
+//! local [`highscores`] leaderboard, the [`movegen`] placement enumeration, a computer
+//! player ([`bot`]), external bots support ([`tbp`]), a reinforcement learning [`env`],
+//! local and networked two-player games ([`versus`], [`net`], [`rollback`]) and
+//! [`spectate`] streaming.
+//! It has no dependency on any GUI toolkit.
+//!
+//! ```
+//! use std::time::Duration;
+//! use tetris_core::{ruleset::Ruleset, tetris::{Event, Tetris}};
+//!
+//! let mut game = Tetris::with_seed(Ruleset::default(), 42);
+//! game.run(Event::Rotate);
+//! game.run(Event::Drop);
+//! game.tick(Duration::from_millis(100));
+//! assert_eq!(game.get_display_state().stats.pieces, 1);
+//! ```
+
+pub mod blocks;
+pub mod bot;
+pub mod clock;
+pub mod env;
+pub mod events;
+pub mod geometry;
+pub mod highscores;
+pub mod movegen;
+pub mod net;
+pub mod replay;
+pub mod rollback;
+pub mod ruleset;
+pub mod session;
+pub mod spectate;
+pub mod statistics;
+pub mod storage;
+pub mod tbp;
+pub mod tetris;
+pub mod tetronimo;
+pub mod versus;
@@ -0,0 +1,226 @@
+//! Move generation: every lock placement the player tetronimo can reach.
+
+use crate::events::EngineEvent;
+use crate::geometry::Position;
+use crate::tetris::{Event, Tetris};
+use crate::tetronimo::Tetronimo;
+use std::collections::{HashSet, VecDeque};
+
+/// Moves explored from each position of the player tetronimo (gravity is not applied
+/// between them, and MoveUp is left out as it is not part of the regular play)
+const MOVES: [Event; 4] = [
+    Event::MoveLeft,
+    Event::MoveRight,
+    Event::MoveDown,
+    Event::Rotate,
+];
+
+/// Lock placement of the player tetronimo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    /// blocks of the locked tetronimo (before clearing the completed rows), top to bottom
+    pub blocks: Vec<Position>,
+    /// true if the tetronimo is locked with a T-spin
+    pub t_spin: bool,
+    /// number of lines completed by the placement
+    pub lines: u8,
+    /// shortest sequence of events reaching the placement, ending with a drop
+    pub events: Vec<Event>,
+}
+
+/// Return every distinct placement the player tetronimo of the game can reach,
+/// including tucks and spins, along with the shortest events leading to it
+pub fn placements(game: &Tetris) -> Vec<Placement> {
+    explore(game)
+        .into_iter()
+        .map(|(placement, _)| placement)
+        .collect()
+}
+
+/// Count the sequences of placements of the given length, playing the tetronimos
+/// of the game in order (used to validate the move generation against known positions)
+pub fn perft(game: &Tetris, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    explore(game)
+        .iter()
+        .map(|(_, next)| perft(next, depth - 1))
+        .sum()
+}
+
+/// Return the placements of the player tetronimo, each with the game once it is locked,
+/// by a breadth first search over the positions reachable with the regular moves
+pub(crate) fn explore(game: &Tetris) -> Vec<(Placement, Tetris)> {
+    let mut placements: Vec<(Placement, Tetris)> = Vec::new();
+    if game.game_over() {
+        return placements;
+    }
+    let mut visited = HashSet::from([key(game)]);
+    let mut queue = VecDeque::from([(game.fork(), Vec::new())]);
+    while let Some((node, path)) = queue.pop_front() {
+        // lock the tetronimo from this position
+        let mut locked = node.fork();
+        let receiver = locked.subscribe();
+        locked.run(Event::Drop);
+        let mut placement = Placement {
+            blocks: Vec::new(),
+            t_spin: false,
+            lines: 0,
+            events: path.iter().copied().chain([Event::Drop]).collect(),
+        };
+        for event in receiver.try_iter() {
+            match event {
+                EngineEvent::PieceLocked { blocks, t_spin, .. } => {
+                    placement.blocks = blocks;
+                    placement.t_spin = t_spin;
+                }
+                EngineEvent::LinesCleared { rows, .. } => placement.lines = rows.len() as u8,
+                _ => {}
+            }
+        }
+        placement.blocks.sort_by_key(|block| (block.y, block.x));
+        // positions are visited by increasing path length, the first path is the shortest
+        if !placements
+            .iter()
+            .any(|(other, _)| other.blocks == placement.blocks && other.t_spin == placement.t_spin)
+        {
+            placements.push((placement, locked));
+        }
+
+        for event in MOVES {
+            let mut next = node.fork();
+            next.run(event);
+            if visited.insert(key(&next)) {
+                let mut next_path = path.clone();
+                next_path.push(event);
+                queue.push_back((next, next_path));
+            }
+        }
+    }
+    placements
+}
+
+/// Return true if the events of a placement still lock the player tetronimo of the game
+/// in its place (e.g. once it fell by gravity since the placement was found)
+pub(crate) fn leads_to(game: &Tetris, placement: &Placement) -> bool {
+    let mut game = game.fork();
+    let receiver = game.subscribe();
+    for &event in &placement.events {
+        game.run(event);
+    }
+    receiver.try_iter().any(|event| match event {
+        EngineEvent::PieceLocked { mut blocks, .. } => {
+            blocks.sort_by_key(|block| (block.y, block.x));
+            blocks == placement.blocks
+        }
+        _ => false,
+    })
+}
+
+/// Return what identifies a position of the player tetronimo during the search
+fn key(game: &Tetris) -> (Tetronimo, bool) {
+    let (tetronimo, last_rotated) = game.player();
+    (tetronimo.clone(), last_rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::EngineEvent,
+        geometry::Position,
+        movegen::{explore, perft, placements},
+        ruleset::Ruleset,
+        tetris::{Event, Tetris},
+        tetronimo::Kind,
+    };
+
+    #[test]
+    fn empty_scene_test() {
+        for seed in 0..20 {
+            let game = Tetris::with_seed(Ruleset::default(), seed);
+            // a 10 wide scene has 10 - width + 1 columns for each distinct orientation
+            let expected = match game.player().0.kind() {
+                Kind::O => 9,
+                Kind::I | Kind::S | Kind::Z => 7 + 10,
+                Kind::J | Kind::L | Kind::T => 2 * (8 + 9),
+            };
+            let found = placements(&game);
+            assert_eq!(found.len(), expected);
+            assert_eq!(perft(&game, 1), expected as u64);
+
+            // the events of each placement lead to it
+            for placement in found {
+                let mut replayed = game.fork();
+                let receiver = replayed.subscribe();
+                for &event in &placement.events {
+                    replayed.run(event);
+                }
+                let locked = receiver.try_iter().find_map(|event| match event {
+                    EngineEvent::PieceLocked { mut blocks, .. } => {
+                        blocks.sort_by_key(|block| (block.y, block.x));
+                        Some(blocks)
+                    }
+                    _ => None,
+                });
+                assert_eq!(locked, Some(placement.blocks));
+            }
+        }
+    }
+
+    #[test]
+    fn perft_test() {
+        // depth 1 on the empty 10 wide scene, for a game starting with each kind
+        let expected = [
+            (Kind::I, 17),
+            (Kind::S, 17),
+            (Kind::Z, 17),
+            (Kind::O, 9),
+            (Kind::J, 34),
+            (Kind::L, 34),
+            (Kind::T, 34),
+        ];
+        for (kind, count) in expected {
+            let game = (0..)
+                .map(|seed| Tetris::with_seed(Ruleset::default(), seed))
+                .find(|game| game.player().0.kind() == kind)
+                .unwrap();
+            assert_eq!(perft(&game, 1), count, "{kind:?}");
+        }
+
+        // the O leaves no overhang: O then O, and O then I (seeds 1 and 0)
+        assert_eq!(perft(&Tetris::with_seed(Ruleset::default(), 1), 2), 9 * 9);
+        assert_eq!(perft(&Tetris::with_seed(Ruleset::default(), 0), 2), 9 * 17);
+        // seed 5 plays a T then a J, seed 7 an L then a J
+        assert_eq!(perft(&Tetris::with_seed(Ruleset::default(), 5), 2), 1180);
+        assert_eq!(perft(&Tetris::with_seed(Ruleset::default(), 7), 2), 1176);
+    }
+
+    #[test]
+    fn tuck_test() {
+        // the L of seed 7 locked as a hook, leaving two free cells under it in column 4:
+        //   LL
+        //   .L
+        //   .L
+        let game = Tetris::with_seed(Ruleset::default(), 7);
+        let hook = [(4, 17), (5, 17), (5, 18), (5, 19)].map(|(x, y)| Position::new(x, y));
+        let (_, next) = explore(&game)
+            .into_iter()
+            .find(|(placement, _)| placement.blocks == hook)
+            .unwrap();
+
+        // the J reaches the 34 placements of the empty scene, and two more tucked under the hook
+        let found = placements(&next);
+        assert_eq!(next.player().0.kind(), Kind::J);
+        assert_eq!(found.len(), 36);
+        let tucked: Vec<_> = found
+            .iter()
+            .filter(|placement| placement.blocks.contains(&Position::new(4, 19)))
+            .collect();
+        assert_eq!(tucked.len(), 2);
+        // both slide right under the hook once down, the second one after a rotation
+        for placement in tucked {
+            assert!(placement.events.ends_with(&[Event::MoveRight, Event::Drop]));
+        }
+    }
+}
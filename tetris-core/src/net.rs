@@ -0,0 +1,568 @@
+//! Networked versus games: two clients kept in lockstep over TCP.
+//!
+//! The host sends the seed and the ruleset of the game, then both clients stream the inputs of
+//! their player for every frame and run a frame once the inputs of both players are known.
+//! The garbage attacks and periodic hashes of the boards are exchanged to detect desyncs.
+//!
+//! The same handshake starts the games of the rollback netcode (see `rollback`).
+
+use crate::ruleset::Ruleset;
+use crate::tetris::{Event, TIMESTEP};
+use crate::versus::Versus;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Version of the network protocol, bumped on every incompatible change
+pub const NET_VERSION: u32 = 3;
+
+/// Interval, in frames, between two comparisons of the board hashes
+const HASH_INTERVAL: u64 = 60;
+
+/// Offset basis and prime of the 64 bits FNV-1a hash of the boards, fixed so that
+/// clients built with different toolchains agree on it
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Maximum number of frames of local inputs sent ahead of the frames run
+const MAX_AHEAD: usize = 8;
+
+/// Time waited for the messages of the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Netcode of a networked game, agreed on by both clients during the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Netcode {
+    /// both games waiting for the inputs of both players
+    Lockstep,
+    /// both games run ahead of the remote inputs, and corrected when they arrive
+    Rollback,
+}
+
+/// Message exchanged by the clients, as a line of JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    // host to joiner: the game to play
+    Hello {
+        version: u32,
+        netcode: Netcode,
+        seed: u64,
+        ruleset: Ruleset,
+    },
+    // joiner to host: game accepted
+    Welcome {
+        version: u32,
+    },
+    // inputs of the sender player for a frame
+    Frame {
+        frame: u64,
+        events: Vec<Event>,
+    },
+    // garbage lines sent by the sender player when running a frame
+    Attack {
+        frame: u64,
+        lines: u32,
+    },
+    // hash of both boards after running a frame
+    Hash {
+        frame: u64,
+        hash: u64,
+    },
+}
+
+/// Error of a networked game
+#[derive(Debug)]
+pub enum NetError {
+    Io(std::io::Error),
+    // message invalid or unexpected at this point of the protocol
+    Protocol(String),
+    // protocol version of the other client
+    Version(u32),
+    // frame at which the games of both clients were found different
+    Desync(u64),
+    Disconnected,
+}
+
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetError::Io(err) => write!(f, "network error: {err}"),
+            NetError::Protocol(reason) => write!(f, "protocol error: {reason}"),
+            NetError::Version(version) => write!(f, "unsupported protocol version {version}"),
+            NetError::Desync(frame) => write!(f, "games out of sync at frame {frame}"),
+            NetError::Disconnected => write!(f, "the other player disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        NetError::Io(err)
+    }
+}
+
+/// Versus game played with a remote client, both running the same game in lockstep
+pub struct NetVersus {
+    versus: Versus,
+    // player controlled locally (0 for the host, 1 for the joiner)
+    local: usize,
+    stream: TcpStream,
+    // messages of the other client, read on a separate thread
+    messages: Receiver<Result<Message, String>>,
+    // number of frames run
+    frame: u64,
+    // time received but not yet turned into frames of local inputs
+    pending: Duration,
+    // local inputs waiting for the next frame sent
+    inputs: Vec<Event>,
+    // inputs of both players for the next frames, sent or received but not run yet
+    local_frames: VecDeque<Vec<Event>>,
+    remote_frames: VecDeque<Vec<Event>>,
+    // attacks and hashes of the local game and of the other client, not compared yet
+    local_checks: VecDeque<Message>,
+    remote_checks: VecDeque<Message>,
+}
+
+impl NetVersus {
+    /// Wait for a client to join, and send it the game to play
+    pub fn host(listener: &TcpListener, ruleset: Ruleset, seed: u64) -> Result<Self, NetError> {
+        let (stream, _) = listener.accept()?;
+        let mut game = Self::connect(stream, 0, ruleset, seed)?;
+        game.send(&hello(Netcode::Lockstep, ruleset, seed))?;
+        check_welcome(game.receive(HANDSHAKE_TIMEOUT)?)?;
+        Ok(game)
+    }
+
+    /// Join the game of a host
+    pub fn join(address: impl ToSocketAddrs) -> Result<Self, NetError> {
+        let stream = TcpStream::connect(address)?;
+        let mut game = Self::connect(stream, 1, Ruleset::default(), 0)?;
+        let (ruleset, seed) = check_hello(game.receive(HANDSHAKE_TIMEOUT)?, Netcode::Lockstep)?;
+        game.versus = Versus::new(ruleset, seed);
+        game.send(&Message::Welcome {
+            version: NET_VERSION,
+        })?;
+        Ok(game)
+    }
+
+    /// Return the versus game
+    pub fn versus(&self) -> &Versus {
+        &self.versus
+    }
+
+    /// Return the player controlled locally
+    pub fn local_player(&self) -> usize {
+        self.local
+    }
+
+    /// Return the number of frames run
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Queue an input of the local player, run with the next frame
+    pub fn run(&mut self, event: Event) {
+        self.inputs.push(event);
+    }
+
+    /// Send the local inputs for the time elapsed, then run the frames whose inputs are known
+    /// for both players (the game stalls while the inputs of the other client are missing)
+    pub fn tick(&mut self, elapsed: Duration) -> Result<(), NetError> {
+        self.pending += elapsed;
+        while self.pending >= TIMESTEP && self.local_frames.len() < MAX_AHEAD {
+            self.pending -= TIMESTEP;
+            let events = std::mem::take(&mut self.inputs);
+            self.send(&Message::Frame {
+                frame: self.frame + self.local_frames.len() as u64,
+                events: events.clone(),
+            })?;
+            self.local_frames.push_back(events);
+        }
+
+        while let Some(message) = self.poll()? {
+            match message {
+                Message::Frame { frame, events } => {
+                    let expected = self.frame + self.remote_frames.len() as u64;
+                    if frame != expected {
+                        return Err(NetError::Protocol(format!(
+                            "received frame {frame} instead of {expected}"
+                        )));
+                    }
+                    self.remote_frames.push_back(events);
+                }
+                Message::Attack { .. } | Message::Hash { .. } => {
+                    self.remote_checks.push_back(message)
+                }
+                message => return Err(unexpected(message)),
+            }
+        }
+
+        while !self.local_frames.is_empty() && !self.remote_frames.is_empty() {
+            self.run_frame()?;
+        }
+        self.compare_checks()
+    }
+
+    /// Run the next frame with the inputs of both players, recording its checks
+    fn run_frame(&mut self) -> Result<(), NetError> {
+        let (Some(local), Some(remote)) = (
+            self.local_frames.pop_front(),
+            self.remote_frames.pop_front(),
+        ) else {
+            return Ok(());
+        };
+        let mut inputs = [local, remote];
+        if self.local == 1 {
+            inputs.reverse();
+        }
+        let sent = [0, 1].map(|player| self.versus.sent(player));
+        for (player, events) in inputs.into_iter().enumerate() {
+            for event in events {
+                self.versus.run(player, event);
+            }
+        }
+        self.versus.tick(TIMESTEP);
+        let frame = self.frame;
+        self.frame += 1;
+
+        // the attacks of both players are checked against the ones of the other client
+        for (player, sent) in sent.into_iter().enumerate() {
+            let lines = self.versus.sent(player) - sent;
+            if lines == 0 {
+                continue;
+            }
+            let attack = Message::Attack { frame, lines };
+            if player == self.local {
+                self.send(&attack)?;
+            } else {
+                self.local_checks.push_back(attack);
+            }
+        }
+        if self.frame.is_multiple_of(HASH_INTERVAL) {
+            let hash = Message::Hash {
+                frame: self.frame,
+                hash: self.board_hash(),
+            };
+            self.send(&hash)?;
+            self.local_checks.push_back(hash);
+        }
+        Ok(())
+    }
+
+    /// Compare the attacks and hashes known on both sides, in order
+    fn compare_checks(&mut self) -> Result<(), NetError> {
+        while let (Some(local), Some(remote)) =
+            (self.local_checks.front(), self.remote_checks.front())
+        {
+            if local != remote {
+                return Err(NetError::Desync(
+                    check_frame(local).min(check_frame(remote)),
+                ));
+            }
+            self.local_checks.pop_front();
+            self.remote_checks.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Return the hash of the boards and pending garbage of both players
+    fn board_hash(&self) -> u64 {
+        let mut bytes = Vec::new();
+        for player in 0..2 {
+            let state = self.versus.engine(player).get_display_state();
+            let mut blocks = state.blocks;
+            blocks.sort_by_key(|block| (block.y, block.x));
+            for positions in [blocks, state.player] {
+                bytes.extend((positions.len() as u32).to_le_bytes());
+                bytes.extend(positions.iter().flat_map(|block| [block.x, block.y]));
+            }
+            bytes.extend(state.score.to_le_bytes());
+            bytes.extend(self.versus.incoming(player).to_le_bytes());
+        }
+        fnv_hash(&bytes)
+    }
+
+    /// Create the game of a connected client, reading its messages on a separate thread
+    fn connect(
+        stream: TcpStream,
+        local: usize,
+        ruleset: Ruleset,
+        seed: u64,
+    ) -> Result<Self, NetError> {
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, messages) = channel();
+        std::thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                let message = serde_json::from_str(&line)
+                    .map_err(|err| format!("invalid message '{line}': {err}"));
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            versus: Versus::new(ruleset, seed),
+            local,
+            stream,
+            messages,
+            frame: 0,
+            pending: Duration::ZERO,
+            inputs: Vec::new(),
+            local_frames: VecDeque::new(),
+            remote_frames: VecDeque::new(),
+            local_checks: VecDeque::new(),
+            remote_checks: VecDeque::new(),
+        })
+    }
+
+    /// Write a message to the other client
+    fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        write_message(&mut self.stream, message)
+    }
+
+    /// Wait for the next message of the other client
+    fn receive(&mut self, timeout: Duration) -> Result<Message, NetError> {
+        match self.messages.recv_timeout(timeout) {
+            Ok(message) => message.map_err(NetError::Protocol),
+            Err(RecvTimeoutError::Timeout) => Err(no_answer()),
+            Err(RecvTimeoutError::Disconnected) => Err(NetError::Disconnected),
+        }
+    }
+
+    /// Return the next message of the other client, if already received
+    fn poll(&mut self) -> Result<Option<Message>, NetError> {
+        match self.messages.try_recv() {
+            Ok(message) => message.map(Some).map_err(NetError::Protocol),
+            Err(std::sync::mpsc::TryRecvError::Empty) => Ok(None),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(NetError::Disconnected),
+        }
+    }
+}
+
+/// Send the game to play to a client joining over a stream, and wait for its acceptance
+/// (the reader of the stream is left at the first message after the handshake)
+pub(crate) fn offer_game(
+    stream: &mut TcpStream,
+    reader: &mut impl BufRead,
+    netcode: Netcode,
+    ruleset: Ruleset,
+    seed: u64,
+) -> Result<(), NetError> {
+    write_message(stream, &hello(netcode, ruleset, seed))?;
+    check_welcome(read_message(stream, reader)?)
+}
+
+/// Wait for the game sent by the host over a stream and accept it, returning its
+/// ruleset and seed (the reader of the stream is left at the first message after the handshake)
+pub(crate) fn accept_game(
+    stream: &mut TcpStream,
+    reader: &mut impl BufRead,
+    netcode: Netcode,
+) -> Result<(Ruleset, u64), NetError> {
+    let game = check_hello(read_message(stream, reader)?, netcode)?;
+    write_message(
+        stream,
+        &Message::Welcome {
+            version: NET_VERSION,
+        },
+    )?;
+    Ok(game)
+}
+
+/// Return the message of the host offering a game
+fn hello(netcode: Netcode, ruleset: Ruleset, seed: u64) -> Message {
+    Message::Hello {
+        version: NET_VERSION,
+        netcode,
+        seed,
+        ruleset,
+    }
+}
+
+/// Check the message of the host offering a game, returning its ruleset and seed
+fn check_hello(message: Message, expected: Netcode) -> Result<(Ruleset, u64), NetError> {
+    match message {
+        Message::Hello { version, .. } if version != NET_VERSION => Err(NetError::Version(version)),
+        Message::Hello { netcode, .. } if netcode != expected => Err(NetError::Protocol(format!(
+            "the host plays with the {netcode:?} netcode"
+        ))),
+        Message::Hello { seed, ruleset, .. } => {
+            ruleset.check().map_err(|reason| {
+                NetError::Protocol(format!("the host sent invalid rules, {reason}"))
+            })?;
+            Ok((ruleset, seed))
+        }
+        message => Err(unexpected(message)),
+    }
+}
+
+/// Check the message of the client accepting a game
+fn check_welcome(message: Message) -> Result<(), NetError> {
+    match message {
+        Message::Welcome { version } if version == NET_VERSION => Ok(()),
+        Message::Welcome { version } => Err(NetError::Version(version)),
+        message => Err(unexpected(message)),
+    }
+}
+
+/// Write a message to a stream
+fn write_message(stream: &mut TcpStream, message: &Message) -> Result<(), NetError> {
+    let line = serde_json::to_string(message).map_err(|err| NetError::Protocol(err.to_string()))?;
+    writeln!(stream, "{line}")?;
+    Ok(())
+}
+
+/// Wait for the next message of the handshake on the reader of a stream
+fn read_message(stream: &TcpStream, reader: &mut impl BufRead) -> Result<Message, NetError> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut line = String::new();
+    let read = reader.read_line(&mut line);
+    stream.set_read_timeout(None)?;
+    match read {
+        Ok(0) => Err(NetError::Disconnected),
+        Ok(_) => serde_json::from_str(&line)
+            .map_err(|err| NetError::Protocol(format!("invalid message '{line}': {err}"))),
+        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            Err(no_answer())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Return the error of a client not answering during the handshake
+fn no_answer() -> NetError {
+    NetError::Protocol("no answer from the other client".to_owned())
+}
+
+/// Return the frame of an attack or hash message
+fn check_frame(message: &Message) -> u64 {
+    match message {
+        Message::Attack { frame, .. } | Message::Hash { frame, .. } => *frame,
+        _ => 0,
+    }
+}
+
+/// Return the 64 bits FNV-1a hash of some bytes
+fn fnv_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Return the error of a message received when another one was expected
+fn unexpected(message: Message) -> NetError {
+    NetError::Protocol(format!("unexpected message {message:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::Position,
+        net::{fnv_hash, hello, write_message, NetError, NetVersus, Netcode},
+        ruleset::Ruleset,
+        tetris::{Event, TIMESTEP},
+    };
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    /// Connect two clients over the loopback interface
+    fn connect() -> (NetVersus, NetVersus) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host =
+            std::thread::spawn(move || NetVersus::host(&listener, Ruleset::default(), 9).unwrap());
+        let joiner = NetVersus::join(address).unwrap();
+        (host.join().unwrap(), joiner)
+    }
+
+    /// Give both clients the time of some frames, then let them run all of them
+    fn play(clients: &mut [NetVersus; 2], frames: u32) -> Result<(), NetError> {
+        for client in clients.iter_mut() {
+            client.pending += TIMESTEP * frames;
+        }
+        let target = clients[0].frame() + frames as u64;
+        while clients.iter().any(|client| client.frame() < target) {
+            for client in clients.iter_mut() {
+                client.tick(Duration::ZERO)?;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lockstep_test() {
+        let (host, joiner) = connect();
+        assert_eq!((host.local_player(), joiner.local_player()), (0, 1));
+        let mut clients = [host, joiner];
+
+        clients[0].run(Event::MoveLeft);
+        clients[0].run(Event::Drop);
+        clients[1].run(Event::Rotate);
+        play(&mut clients, 50).unwrap();
+        clients[1].run(Event::Drop);
+        play(&mut clients, 100).unwrap();
+
+        // both clients run the same game, each controlling its own player
+        for player in 0..2 {
+            assert_eq!(
+                clients[0].versus().engine(player).get_display_state(),
+                clients[1].versus().engine(player).get_display_state()
+            );
+            assert_eq!(
+                clients[player]
+                    .versus()
+                    .engine(player)
+                    .get_display_state()
+                    .stats
+                    .pieces,
+                1
+            );
+        }
+        assert!(clients.iter().all(|client| client.local_checks.is_empty()));
+    }
+
+    #[test]
+    fn fnv_hash_test() {
+        // reference values of the 64 bits FNV-1a hash
+        assert_eq!(fnv_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn invalid_rules_test() {
+        // rules the engine can't play are rejected by the joiner
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let ruleset = Ruleset {
+                scene_area: Position::new(0, 0),
+                ..Ruleset::default()
+            };
+            write_message(&mut stream, &hello(Netcode::Lockstep, ruleset, 9)).unwrap();
+            let _ = std::io::read_to_string(stream);
+        });
+        let joined = NetVersus::join(address);
+        assert!(matches!(joined, Err(NetError::Protocol(_))));
+    }
+
+    #[test]
+    fn desync_test() {
+        let (host, joiner) = connect();
+        let mut clients = [host, joiner];
+        // a game diverging from the other one is detected at the next hash comparison
+        clients[0].versus.run(1, Event::Drop);
+        let result = play(&mut clients, 70);
+        assert!(matches!(result, Err(NetError::Desync(60))));
+    }
+}
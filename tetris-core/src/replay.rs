@@ -0,0 +1,202 @@
+//! Recording of games and their playback.
+
+use crate::ruleset::Ruleset;
+use crate::storage::{check_version, load_json, save_json, StorageError};
+use crate::tetris::{Event, Tetris, TIMESTEP};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Version of the replay file format, bumped on every incompatible change
+pub const REPLAY_VERSION: u32 = 2;
+
+/// Event recorded in a replay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    /// engine frame (number of time steps since the game creation) of the event
+    pub frame: u64,
+    pub event: Event,
+}
+
+/// Recording of a game: everything needed to reproduce it from scratch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// seed of the random sequence of tetronimos
+    pub seed: u64,
+    /// rules of the game (size of the scene area, timings)
+    pub ruleset: Ruleset,
+    /// total number of engine frames of the game
+    pub frames: u64,
+    /// all the events run by the engine, in order
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Create an empty recording for a game
+    pub fn new(seed: u64, ruleset: Ruleset) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            ruleset,
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Append an event to the recording
+    pub fn record(&mut self, frame: u64, event: Event) {
+        self.events.push(ReplayEvent { frame, event });
+    }
+
+    /// Write the replay to a file
+    pub fn save(&self, path: &std::path::Path) -> Result<(), StorageError> {
+        save_json(path, self)
+    }
+
+    /// Read a replay from a file, rejecting unknown format versions
+    pub fn load(path: &std::path::Path) -> Result<Self, StorageError> {
+        let replay: Replay = load_json(path)?;
+        check_version(replay.version, REPLAY_VERSION)?;
+        Ok(replay)
+    }
+}
+
+/// Playback driver: feeds the events of a replay into a fresh game engine
+pub struct ReplayPlayer {
+    replay: Replay,
+    // engine reproducing the recorded game
+    engine: Tetris,
+    // index of the next event to run
+    cursor: usize,
+    // playback time not yet run as engine time steps
+    pending: Duration,
+    /// playback speed factor (1.0 is real time)
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl ReplayPlayer {
+    /// Create a player of a replay, paused at its beginning
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            engine: Tetris::with_seed(replay.ruleset, replay.seed),
+            replay,
+            cursor: 0,
+            pending: Duration::ZERO,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Advance the playback by a (real) time interval, scaled by the playback speed
+    pub fn advance(&mut self, elapsed: Duration) {
+        if self.paused {
+            return;
+        }
+        self.pending += elapsed.mul_f32(self.speed);
+        while self.pending >= TIMESTEP && !self.finished() {
+            self.pending -= TIMESTEP;
+            self.step();
+        }
+    }
+
+    /// Run all the remaining frames and events at once
+    pub fn run_to_end(&mut self) {
+        while !self.finished() {
+            self.step();
+        }
+    }
+
+    /// Return true when all the frames and events of the replay were run
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.replay.events.len()
+            && (self.engine.frame() >= self.replay.frames || self.engine.game_over())
+    }
+
+    /// Return the engine reproducing the recorded game
+    pub fn engine(&self) -> &Tetris {
+        &self.engine
+    }
+
+    /// Run the events of the current frame, then advance the engine to the next frame
+    fn step(&mut self) {
+        while let Some(recorded) = self.replay.events.get(self.cursor) {
+            if recorded.frame > self.engine.frame() {
+                break;
+            }
+            self.engine.run(recorded.event);
+            self.cursor += 1;
+        }
+        if self.engine.frame() < self.replay.frames {
+            self.engine.tick(TIMESTEP);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        replay::{Replay, ReplayPlayer},
+        ruleset::Ruleset,
+        tetris::{Event, Tetris},
+    };
+    use std::time::Duration;
+
+    fn play_game(seed: u64) -> Tetris {
+        let mut game = Tetris::with_seed(Ruleset::default(), seed);
+        let inputs = [
+            Event::ShiftLeft,
+            Event::Rotate,
+            Event::ShiftStop,
+            Event::MoveRight,
+            Event::MoveDown,
+            Event::Drop,
+        ];
+        for round in 0..120 {
+            game.run(inputs[round % inputs.len()]);
+            // uneven frame durations, as in the GUI
+            game.tick(Duration::from_millis(5 + (round as u64 * 7) % 40));
+        }
+        game
+    }
+
+    #[test]
+    fn playback_reproduces_game_test() {
+        let game = play_game(42);
+        let mut player = ReplayPlayer::new(game.replay().clone());
+        player.run_to_end();
+        assert!(player.finished());
+        assert_eq!(
+            game.get_display_state(),
+            player.engine().get_display_state()
+        );
+    }
+
+    #[test]
+    fn playback_follows_timestamps_test() {
+        let mut replay = Replay::new(7, Ruleset::default());
+        replay.record(6, Event::MoveLeft);
+        replay.record(18, Event::MoveLeft);
+        replay.frames = 20;
+        let mut player = ReplayPlayer::new(replay);
+        player.advance(Duration::from_millis(150));
+        assert_eq!((player.cursor, player.engine.frame()), (1, 9));
+        player.paused = true;
+        player.advance(Duration::from_millis(500));
+        assert_eq!(player.engine.frame(), 9);
+        player.paused = false;
+        player.speed = 2.0;
+        player.advance(Duration::from_millis(200));
+        assert!(player.finished());
+        assert_eq!(player.engine.frame(), 20);
+    }
+
+    #[test]
+    fn save_and_load_test() {
+        let replay = play_game(3).replay().clone();
+        let path = std::env::temp_dir().join("tetris_replay_test.json");
+        replay.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap(), replay);
+        std::fs::remove_file(path).unwrap();
+    }
+}
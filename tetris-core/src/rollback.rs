@@ -0,0 +1,307 @@
+//! Rollback netcode: versus games run ahead of the remote inputs, and corrected when they arrive.
+//!
+//! Each client runs its frames without waiting for the other one, predicting that the remote
+//! player gives no input (held moves carry on in the engine). A snapshot of the game is kept
+//! for each of the last frames, so that when a remote input arrives late the game is restored
+//! to the frame of the input and run again up to the current one.
+//!
+//! Over TCP, the host sends the seed and the ruleset of the game with the handshake of `net`.
+
+use crate::net::{accept_game, offer_game, NetError, Netcode};
+use crate::ruleset::Ruleset;
+use crate::tetris::{Event, TIMESTEP};
+use crate::versus::Versus;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::time::Duration;
+
+/// Maximum number of frames run ahead of the last frame with the inputs of both players
+pub const MAX_ROLLBACK: usize = 8;
+
+/// Inputs of a player for a frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packet {
+    pub frame: u64,
+    pub events: Vec<Event>,
+}
+
+/// Channel carrying the packets between the clients (they may arrive out of order)
+pub trait Transport {
+    /// Send a packet to the other client
+    fn send(&mut self, packet: Packet) -> Result<(), NetError>;
+    /// Return the packets received since the last call
+    fn receive(&mut self) -> Result<Vec<Packet>, NetError>;
+}
+
+/// Transport over a TCP stream, one line of JSON per packet
+pub struct TcpTransport {
+    stream: TcpStream,
+    // packets of the other client, read on a separate thread
+    packets: Receiver<Result<Packet, String>>,
+}
+
+impl TcpTransport {
+    /// Use a stream connected to the other client
+    pub fn new(stream: TcpStream) -> Result<Self, NetError> {
+        let reader = BufReader::new(stream.try_clone()?);
+        Self::start(stream, reader)
+    }
+
+    /// Use a stream connected to the other client, the packets read from its reader
+    /// (which may have buffered the first ones during the handshake)
+    fn start(stream: TcpStream, reader: BufReader<TcpStream>) -> Result<Self, NetError> {
+        stream.set_nodelay(true)?;
+        let (sender, packets) = channel();
+        std::thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                let packet = serde_json::from_str(&line)
+                    .map_err(|err| format!("invalid packet '{line}': {err}"));
+                if sender.send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { stream, packets })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, packet: Packet) -> Result<(), NetError> {
+        let line =
+            serde_json::to_string(&packet).map_err(|err| NetError::Protocol(err.to_string()))?;
+        writeln!(self.stream, "{line}")?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<Packet>, NetError> {
+        let mut packets = Vec::new();
+        loop {
+            match self.packets.try_recv() {
+                Ok(packet) => packets.push(packet.map_err(NetError::Protocol)?),
+                Err(TryRecvError::Empty) => return Ok(packets),
+                Err(TryRecvError::Disconnected) => return Err(NetError::Disconnected),
+            }
+        }
+    }
+}
+
+/// In-process transport delaying the packets by a number of receive calls,
+/// with a random jitter reordering them (to test the netcode under latency)
+pub struct LoopbackTransport {
+    sender: Sender<Packet>,
+    receiver: Receiver<Packet>,
+    // packets received, with the receive calls left before their delivery
+    inbox: Vec<(u32, Packet)>,
+    latency: u32,
+    jitter: u32,
+    // random number generator of the jitter
+    rng: ChaCha12Rng,
+}
+
+impl LoopbackTransport {
+    /// Create both ends of a transport
+    pub fn pair(latency: u32, jitter: u32, seed: u64) -> (Self, Self) {
+        let (first_sender, first_receiver) = channel();
+        let (second_sender, second_receiver) = channel();
+        let end = |sender, receiver, seed| Self {
+            sender,
+            receiver,
+            inbox: Vec::new(),
+            latency,
+            jitter,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        };
+        (
+            end(first_sender, second_receiver, seed),
+            end(second_sender, first_receiver, seed + 1),
+        )
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, packet: Packet) -> Result<(), NetError> {
+        self.sender.send(packet).map_err(|_| NetError::Disconnected)
+    }
+
+    fn receive(&mut self) -> Result<Vec<Packet>, NetError> {
+        for packet in self.receiver.try_iter() {
+            let delay = self.latency + self.rng.gen_range(0..=self.jitter);
+            self.inbox.push((delay, packet));
+        }
+        let mut delivered = Vec::new();
+        self.inbox.retain_mut(|(delay, packet)| match delay {
+            0 => {
+                delivered.push(packet.clone());
+                false
+            }
+            _ => {
+                *delay -= 1;
+                true
+            }
+        });
+        Ok(delivered)
+    }
+}
+
+/// Versus game played with a remote client, run ahead of its inputs and rolled back on them
+pub struct RollbackVersus<T: Transport> {
+    versus: Versus,
+    // player controlled locally
+    local: usize,
+    transport: T,
+    // number of frames run
+    frame: u64,
+    // number of frames run with the inputs of both players
+    confirmed: u64,
+    // time received but not yet run as frames
+    pending: Duration,
+    // local inputs waiting for the next frame run
+    inputs: Vec<Event>,
+    // local inputs of the frames run from the confirmed one
+    local_frames: VecDeque<Vec<Event>>,
+    // remote inputs received for the frames from the confirmed one
+    remote_frames: BTreeMap<u64, Vec<Event>>,
+    // game at the start of the last frames run, indexed by frame modulo MAX_ROLLBACK
+    snapshots: Vec<Versus>,
+    // number of times the game was rolled back
+    rollbacks: u64,
+}
+
+impl<T: Transport> RollbackVersus<T> {
+    /// Create the game of a client (both clients must agree on the ruleset and the seed)
+    pub fn new(ruleset: Ruleset, seed: u64, local: usize, transport: T) -> Self {
+        let versus = Versus::new(ruleset, seed);
+        Self {
+            snapshots: vec![versus.clone(); MAX_ROLLBACK],
+            versus,
+            local,
+            transport,
+            frame: 0,
+            confirmed: 0,
+            pending: Duration::ZERO,
+            inputs: Vec::new(),
+            local_frames: VecDeque::new(),
+            remote_frames: BTreeMap::new(),
+            rollbacks: 0,
+        }
+    }
+
+    /// Return the versus game (predicted after the confirmed frame)
+    pub fn versus(&self) -> &Versus {
+        &self.versus
+    }
+
+    /// Return the player controlled locally
+    pub fn local_player(&self) -> usize {
+        self.local
+    }
+
+    /// Return the number of frames run
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Return the number of frames run with the inputs of both players
+    pub fn confirmed_frame(&self) -> u64 {
+        self.confirmed
+    }
+
+    /// Return the number of times the game was rolled back to correct a prediction
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    /// Queue an input of the local player, run with the next frame
+    pub fn run(&mut self, event: Event) {
+        self.inputs.push(event);
+    }
+
+    /// Apply the remote inputs received, rolling back if they differ from the predictions,
+    /// then run the frames of the time elapsed (stalling when too far ahead of the other client)
+    pub fn tick(&mut self, elapsed: Duration) -> Result<(), NetError> {
+        self.pending += elapsed;
+
+        let mut rollback: Option<u64> = None;
+        for packet in self.transport.receive()? {
+            if packet.frame < self.confirmed || self.remote_frames.contains_key(&packet.frame) {
+                continue;
+            }
+            // the frames already run predicted no remote input
+            if packet.frame < self.frame && !packet.events.is_empty() {
+                rollback = Some(rollback.map_or(packet.frame, |frame| frame.min(packet.frame)));
+            }
+            self.remote_frames.insert(packet.frame, packet.events);
+        }
+        if let Some(frame) = rollback {
+            self.versus = self.snapshots[frame as usize % MAX_ROLLBACK].clone();
+            self.rollbacks += 1;
+            for frame in frame..self.frame {
+                self.simulate(frame);
+            }
+        }
+        while self.confirmed < self.frame && self.remote_frames.contains_key(&self.confirmed) {
+            self.remote_frames.remove(&self.confirmed);
+            self.local_frames.pop_front();
+            self.confirmed += 1;
+        }
+
+        while self.pending >= TIMESTEP && self.frame - self.confirmed < MAX_ROLLBACK as u64 {
+            self.pending -= TIMESTEP;
+            let events = std::mem::take(&mut self.inputs);
+            self.transport.send(Packet {
+                frame: self.frame,
+                events: events.clone(),
+            })?;
+            self.local_frames.push_back(events);
+            self.simulate(self.frame);
+            self.frame += 1;
+        }
+        Ok(())
+    }
+
+    /// Run a frame with the local inputs and the remote ones (none if not received yet),
+    /// keeping a snapshot of the game before it
+    fn simulate(&mut self, frame: u64) {
+        self.snapshots[frame as usize % MAX_ROLLBACK] = self.versus.clone();
+        let local = &self.local_frames[(frame - self.confirmed) as usize];
+        let remote = self
+            .remote_frames
+            .get(&frame)
+            .map_or(&[][..], Vec::as_slice);
+        let mut inputs = [local.as_slice(), remote];
+        if self.local == 1 {
+            inputs.reverse();
+        }
+        for (player, events) in inputs.into_iter().enumerate() {
+            for &event in events {
+                self.versus.run(player, event);
+            }
+        }
+        self.versus.tick(TIMESTEP);
+    }
+}
+
+impl RollbackVersus<TcpTransport> {
+    /// Wait for a client to join, and send it the game to play
+    pub fn host(listener: &TcpListener, ruleset: Ruleset, seed: u64) -> Result<Self, NetError> {
+        let (mut stream, _) = listener.accept()?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        offer_game(&mut stream, &mut reader, Netcode::Rollback, ruleset, seed)?;
+        let transport = TcpTransport::start(stream, reader)?;
+        Ok(Self::new(ruleset, seed, 0, transport))
+    }
+
+    /// Join the game of a host
+    pub fn join(address: impl ToSocketAddrs) -> Result<Self, NetError> {
+        let mut stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (ruleset, seed) = accept_game(&mut stream, &mut reader, Netcode::Rollback)?;
+        let transport = TcpTransport::start(stream, reader)?;
+        Ok(Self::new(ruleset, seed, 1, transport))
+    }
+}
@@ -0,0 +1,104 @@
+//! Rules of a game: size of the scene area and engine timings.
+
+use crate::geometry::Position;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Smallest and largest scene areas the engine can play in
+pub const MIN_SCENE_AREA: Position = Position::new(4, 4);
+pub const MAX_SCENE_AREA: Position = Position::new(64, 64);
+
+/// Rules of a game: size of the scene area and timings of the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ruleset {
+    /// area available for the game scene, represented by the bottom_right position
+    pub scene_area: Position,
+    /// time a tetronimo can rest on the stash before being locked
+    pub lock_delay: Duration,
+    /// time a new tetronimo waits at the top before gravity applies to it (ARE)
+    pub entry_delay: Duration,
+    /// time a horizontal move must be held before it starts repeating (DAS)
+    pub das: Duration,
+    /// interval between the repeated horizontal moves (ARR, zero moves to the wall at once)
+    pub arr: Duration,
+    /// time the auto repeat pauses after a rotation or a spawn (DAS cut delay)
+    pub dcd: Duration,
+    /// factor speeding up the gravity while the soft drop is held
+    pub soft_drop_factor: u32,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self {
+            scene_area: Position::new(10, 20),
+            lock_delay: Duration::from_millis(500),
+            entry_delay: Duration::from_millis(100),
+            das: Duration::from_millis(167),
+            arr: Duration::from_millis(33),
+            dcd: Duration::ZERO,
+            soft_drop_factor: 20,
+        }
+    }
+}
+
+impl Ruleset {
+    /// Check that the rules can be played (e.g. once read from a file or received from
+    /// another client), returning the reason otherwise
+    pub fn check(&self) -> Result<(), String> {
+        let area = self.scene_area;
+        if !(MIN_SCENE_AREA.x..=MAX_SCENE_AREA.x).contains(&area.x)
+            || !(MIN_SCENE_AREA.y..=MAX_SCENE_AREA.y).contains(&area.y)
+        {
+            return Err(format!(
+                "scene area {}x{} out of the {}x{} to {}x{} range",
+                area.x,
+                area.y,
+                MIN_SCENE_AREA.x,
+                MIN_SCENE_AREA.y,
+                MAX_SCENE_AREA.x,
+                MAX_SCENE_AREA.y
+            ));
+        }
+        if self.soft_drop_factor == 0 {
+            return Err("soft drop factor of 0".to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        geometry::Position,
+        ruleset::{Ruleset, MAX_SCENE_AREA, MIN_SCENE_AREA},
+    };
+
+    #[test]
+    fn check_test() {
+        assert_eq!(Ruleset::default().check(), Ok(()));
+        for scene_area in [MIN_SCENE_AREA, MAX_SCENE_AREA] {
+            let ruleset = Ruleset {
+                scene_area,
+                ..Ruleset::default()
+            };
+            assert_eq!(ruleset.check(), Ok(()));
+        }
+        for scene_area in [
+            Position::new(0, 0),
+            Position::new(3, 20),
+            Position::new(10, 65),
+        ] {
+            let ruleset = Ruleset {
+                scene_area,
+                ..Ruleset::default()
+            };
+            assert!(ruleset.check().is_err());
+        }
+        let ruleset = Ruleset {
+            soft_drop_factor: 0,
+            ..Ruleset::default()
+        };
+        assert!(ruleset.check().is_err());
+    }
+}
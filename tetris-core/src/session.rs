@@ -0,0 +1,137 @@
+//! Game session controller, running the engine loop.
+
+use crate::clock::Clock;
+use crate::ruleset::Ruleset;
+use crate::tetris::{Event, Tetris};
+
+/// Controller of a game session: owns the game engine along with the clock driving it,
+/// so a single loop advances the game, and only while the session is running
+pub struct Session<C: Clock> {
+    engine: Tetris,
+    clock: C,
+    running: bool,
+}
+
+impl<C: Clock> Session<C> {
+    /// Create a stopped session for a game engine
+    pub fn new(engine: Tetris, clock: C) -> Self {
+        Self {
+            engine,
+            clock,
+            running: false,
+        }
+    }
+
+    /// Start (or resume) running the game, unless already running or over
+    pub fn start(&mut self) {
+        if !self.running && !self.engine.game_over() {
+            // discard the time elapsed while stopped
+            self.clock.elapsed();
+            self.running = true;
+        }
+    }
+
+    /// Stop running the game, cancelling the loop until started again
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Start a new game, right away
+    pub fn restart(&mut self) {
+        self.stop();
+        self.engine.reset();
+        self.start();
+    }
+
+    /// Start a new game with other rules, right away
+    pub fn restart_with(&mut self, ruleset: Ruleset) {
+        self.stop();
+        self.engine.reset_with(ruleset);
+        self.start();
+    }
+
+    /// Return true while the game is running
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Run a player event, if the game is running
+    pub fn run(&mut self, event: Event) {
+        if self.running {
+            self.engine.run(event);
+        }
+    }
+
+    /// Advance the game by the time elapsed on the clock since the previous update,
+    /// stopping the session once the game is over
+    pub fn update(&mut self) {
+        if self.running {
+            self.engine.advance(&mut self.clock);
+            self.running = !self.engine.game_over();
+        }
+    }
+
+    /// Return the game engine
+    pub fn engine(&self) -> &Tetris {
+        &self.engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::Clock,
+        ruleset::Ruleset,
+        session::Session,
+        tetris::{Event, Tetris},
+    };
+    use std::time::Duration;
+
+    // clock advancing by a fixed amount at each call
+    struct StepClock(Duration);
+
+    impl Clock for StepClock {
+        fn elapsed(&mut self) -> Duration {
+            self.0
+        }
+    }
+
+    #[test]
+    fn session_test() {
+        let engine = Tetris::with_seed(Ruleset::default(), 9);
+        let mut session = Session::new(engine, StepClock(Duration::from_millis(17)));
+
+        // nothing happens before the start
+        session.update();
+        session.run(Event::Drop);
+        assert_eq!(session.engine().frame(), 0);
+        assert_eq!(session.engine().get_display_state().stats.pieces, 0);
+
+        // starting twice does not run the game twice as fast
+        session.start();
+        session.start();
+        session.update();
+        assert_eq!(session.engine().frame(), 1);
+
+        session.stop();
+        session.update();
+        assert_eq!(session.engine().frame(), 1);
+
+        // a restart begins a new running game
+        session.start();
+        session.run(Event::Drop);
+        session.restart();
+        assert!(session.is_running());
+        assert_eq!(session.engine().frame(), 0);
+        assert_eq!(session.engine().get_display_state().stats.pieces, 0);
+
+        // the session stops by itself at game over
+        while !session.engine().game_over() {
+            session.run(Event::Drop);
+        }
+        session.update();
+        assert!(!session.is_running());
+        session.start();
+        assert!(!session.is_running());
+    }
+}
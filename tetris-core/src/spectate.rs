@@ -0,0 +1,197 @@
+//! Spectator streaming: display states broadcast as lines of JSON over TCP.
+
+use crate::tetris::DisplayState;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+
+/// Error of a spectator connection
+#[derive(Debug)]
+pub enum SpectateError {
+    Io(std::io::Error),
+    // line that is not a display state
+    Protocol(String),
+    Disconnected,
+}
+
+impl std::fmt::Display for SpectateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpectateError::Io(err) => write!(f, "network error: {err}"),
+            SpectateError::Protocol(reason) => write!(f, "protocol error: {reason}"),
+            SpectateError::Disconnected => write!(f, "the game stopped broadcasting"),
+        }
+    }
+}
+
+impl std::error::Error for SpectateError {}
+
+impl From<std::io::Error> for SpectateError {
+    fn from(err: std::io::Error) -> Self {
+        SpectateError::Io(err)
+    }
+}
+
+/// Server broadcasting the display states of a game to its spectators
+pub struct SpectatorServer {
+    // listener accepting the spectators, without blocking
+    listener: TcpListener,
+    // channels of the threads writing to each spectator
+    spectators: Vec<Sender<Arc<str>>>,
+    // last display state broadcast, as a line of JSON
+    last: Option<Arc<str>>,
+}
+
+impl SpectatorServer {
+    /// Listen for spectators on the address
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self, SpectateError> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            spectators: Vec::new(),
+            last: None,
+        })
+    }
+
+    /// Return the address listened on
+    pub fn local_addr(&self) -> Result<SocketAddr, SpectateError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Return the number of connected spectators
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Accept the new spectators, then send them the display state
+    /// (only sent to the others when it changed since the last broadcast)
+    pub fn broadcast(&mut self, state: &DisplayState) -> Result<(), SpectateError> {
+        let line: Arc<str> = serde_json::to_string(state)
+            .map_err(|err| SpectateError::Protocol(err.to_string()))?
+            .into();
+        let changed = self.last.as_ref() != Some(&line);
+        self.last = Some(line.clone());
+
+        if changed {
+            self.spectators
+                .retain(|spectator| spectator.send(line.clone()).is_ok());
+        }
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    let spectator = spawn_writer(stream);
+                    if spectator.send(line.clone()).is_ok() {
+                        self.spectators.push(spectator);
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Write the lines sent to a spectator on a separate thread, so slow ones do not block the game
+/// (the thread ends, disconnecting the spectator from the server, once a write fails)
+fn spawn_writer(mut stream: TcpStream) -> Sender<Arc<str>> {
+    let (sender, lines) = channel::<Arc<str>>();
+    std::thread::spawn(move || {
+        for line in lines {
+            if writeln!(stream, "{line}").is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Client receiving the display states broadcast by a game
+pub struct SpectatorClient {
+    // display states read on a separate thread
+    states: Receiver<Result<DisplayState, SpectateError>>,
+}
+
+impl SpectatorClient {
+    /// Connect to a spectator server (in the background, failures are returned by `latest`)
+    pub fn connect(address: impl ToSocketAddrs + Send + 'static) -> Self {
+        let (sender, states) = channel();
+        std::thread::spawn(move || {
+            let stream = match TcpStream::connect(address) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    let _ = sender.send(Err(err.into()));
+                    return;
+                }
+            };
+            for line in BufReader::new(stream).lines() {
+                let state = match line {
+                    Ok(line) => serde_json::from_str(&line).map_err(|err| {
+                        SpectateError::Protocol(format!("invalid display state: {err}"))
+                    }),
+                    Err(err) => Err(err.into()),
+                };
+                let failed = state.is_err();
+                if sender.send(state).is_err() || failed {
+                    return;
+                }
+            }
+            let _ = sender.send(Err(SpectateError::Disconnected));
+        });
+        Self { states }
+    }
+
+    /// Return the last display state received since the previous call, if any
+    pub fn latest(&mut self) -> Result<Option<DisplayState>, SpectateError> {
+        let mut latest = None;
+        loop {
+            match self.states.try_recv() {
+                Ok(state) => latest = Some(state?),
+                Err(TryRecvError::Empty) => return Ok(latest),
+                Err(TryRecvError::Disconnected) => return Err(SpectateError::Disconnected),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ruleset::Ruleset,
+        spectate::{SpectateError, SpectatorClient, SpectatorServer},
+        tetris::{Event, Tetris},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn broadcast_test() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let mut client = SpectatorClient::connect(server.local_addr().unwrap());
+        let mut game = Tetris::with_seed(Ruleset::default(), 1);
+        game.run(Event::Drop);
+        let state = game.get_display_state();
+
+        // the spectator receives the state once accepted
+        let received = loop {
+            server.broadcast(&state).unwrap();
+            if let Some(received) = client.latest().unwrap() {
+                break received;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(received, state);
+        assert_eq!(server.spectators(), 1);
+
+        // and is disconnected when the game stops broadcasting
+        drop(server);
+        let error = loop {
+            match client.latest() {
+                Ok(_) => std::thread::sleep(Duration::from_millis(10)),
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(error, SpectateError::Disconnected));
+    }
+}
@@ -0,0 +1,99 @@
+//! Statistics collected along a game.
+
+use crate::tetronimo::Kind;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Statistics collected along a game
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    /// number of tetronimos locked in the stash
+    pub pieces: u32,
+    /// number of player inputs (key presses)
+    pub inputs: u32,
+    /// number of singles, doubles, triples and tetrises
+    pub clears: [u32; 4],
+    /// number of tetronimos locked by a T-spin
+    pub t_spins: u32,
+    /// current and longest streaks of consecutive pieces clearing lines
+    pub combo: u32,
+    pub max_combo: u32,
+    /// number of locked tetronimos by kind (indexed as Kind::ALL)
+    pub piece_counts: [u32; 7],
+    /// game time elapsed
+    pub elapsed: Duration,
+}
+
+impl Statistics {
+    /// Register a player input
+    pub fn record_input(&mut self) {
+        self.inputs += 1;
+    }
+
+    /// Register a tetronimo locked in the stash, with the number of lines it cleared
+    pub fn record_lock(&mut self, kind: Kind, cleared_lines: u8, t_spin: bool) {
+        self.pieces += 1;
+        self.piece_counts[kind as usize] += 1;
+        self.t_spins += t_spin as u32;
+        match cleared_lines {
+            0 => self.combo = 0,
+            lines => {
+                self.clears[(lines.min(4) - 1) as usize] += 1;
+                self.combo += 1;
+                self.max_combo = self.max_combo.max(self.combo);
+            }
+        }
+    }
+
+    /// Total number of lines cleared
+    pub fn lines(&self) -> u32 {
+        self.clears
+            .iter()
+            .zip(1..)
+            .map(|(count, lines)| count * lines)
+            .sum()
+    }
+
+    /// Pieces locked per second
+    pub fn pieces_per_second(&self) -> f32 {
+        match self.elapsed.as_secs_f32() {
+            secs if secs > 0. => self.pieces as f32 / secs,
+            _ => 0.,
+        }
+    }
+
+    /// Player inputs per locked piece
+    pub fn inputs_per_piece(&self) -> f32 {
+        match self.pieces {
+            0 => 0.,
+            pieces => self.inputs as f32 / pieces as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{statistics::Statistics, tetronimo::Kind};
+    use std::time::Duration;
+
+    #[test]
+    fn statistics_test() {
+        let mut stats = Statistics::default();
+        stats.record_input();
+        stats.record_input();
+        stats.elapsed = Duration::from_secs(2);
+        stats.record_lock(Kind::T, 2, true);
+        stats.record_lock(Kind::I, 4, false);
+        stats.record_lock(Kind::O, 0, false);
+        stats.record_lock(Kind::I, 1, false);
+
+        assert_eq!(stats.elapsed, Duration::from_secs(2));
+        assert_eq!(stats.pieces_per_second(), 2.);
+        assert_eq!(stats.inputs_per_piece(), 0.5);
+        assert_eq!(stats.clears, [1, 1, 0, 1]);
+        assert_eq!(stats.lines(), 7);
+        assert_eq!(stats.t_spins, 1);
+        assert_eq!((stats.combo, stats.max_combo), (1, 2));
+        assert_eq!(stats.piece_counts, [2, 0, 0, 1, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,56 @@
+//! JSON file persistence helpers.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+/// Error reading or writing a file
+#[derive(Debug)]
+pub enum StorageError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::Io(err) => write!(f, "cannot access file: {err}"),
+            StorageError::Format(err) => write!(f, "invalid file: {err}"),
+            StorageError::Version(version) => write!(f, "unsupported file version {version}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Format(err)
+    }
+}
+
+/// Write a value to a file, as JSON
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    Ok(serde_json::to_writer(file, value)?)
+}
+
+/// Read a value from a JSON file
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<T, StorageError> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    Ok(serde_json::from_reader(file)?)
+}
+
+/// Check the version read from a file against the one supported
+pub fn check_version(found: u32, expected: u32) -> Result<(), StorageError> {
+    match found == expected {
+        true => Ok(()),
+        false => Err(StorageError::Version(found)),
+    }
+}
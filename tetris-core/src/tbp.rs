@@ -0,0 +1,333 @@
+//! Tetris Bot Protocol (TBP): external bots playing the game over JSON messages on stdio.
+
+use crate::geometry::Position;
+use crate::movegen;
+use crate::tetris::Tetris;
+use crate::tetronimo::Kind;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Number of columns of the protocol board
+pub const BOARD_WIDTH: usize = 10;
+/// Number of rows of the protocol board (the scene area fills the bottom ones)
+pub const BOARD_HEIGHT: usize = 40;
+
+/// Rotation state of a piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// Spin announced for a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// Location of a piece: its shape, rotation and center cell
+/// (columns counted from the left and rows from the bottom of the board)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PieceLocation {
+    /// Return the cells covered by the piece (columns from the left, rows from the bottom)
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        let shape = match self.kind {
+            Kind::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Kind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Kind::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Kind::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Kind::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Kind::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Kind::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        };
+        shape.map(|(x, y)| {
+            let (x, y) = match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            };
+            (self.x + x, self.y + y)
+        })
+    }
+}
+
+/// Move of a piece to its lock location
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+/// Message sent by the game to the bot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<Kind>,
+        queue: Vec<Kind>,
+        combo: u32,
+        back_to_back: bool,
+        // rows of cells from the bottom of the board ('G' for the stashed blocks)
+        board: Vec<Vec<Option<char>>>,
+    },
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: Kind,
+    },
+    Stop,
+    Quit,
+}
+
+/// Message sent by the bot to the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+/// Error communicating with an external bot
+#[derive(Debug)]
+pub enum TbpError {
+    Io(std::io::Error),
+    // message invalid or unexpected at this point of the protocol
+    Protocol(String),
+    // error reported by the bot
+    Rejected(String),
+    Timeout,
+    Closed,
+}
+
+impl std::fmt::Display for TbpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TbpError::Io(err) => write!(f, "cannot communicate with the bot: {err}"),
+            TbpError::Protocol(reason) => write!(f, "protocol error: {reason}"),
+            TbpError::Rejected(reason) => write!(f, "bot error: {reason}"),
+            TbpError::Timeout => write!(f, "the bot did not answer in time"),
+            TbpError::Closed => write!(f, "the bot closed its output"),
+        }
+    }
+}
+
+impl std::error::Error for TbpError {}
+
+impl From<std::io::Error> for TbpError {
+    fn from(err: std::io::Error) -> Self {
+        TbpError::Io(err)
+    }
+}
+
+/// External bot process, spoken to with the Tetris Bot Protocol
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    // lines written by the bot, read on a separate thread so waiting for them can time out
+    lines: Receiver<String>,
+    // time the bot has to answer each message
+    timeout: Duration,
+    name: String,
+}
+
+impl ExternalBot {
+    /// Spawn the bot process, then wait for its info and for it to accept the rules
+    pub fn spawn(command: &mut Command, timeout: Duration) -> Result<Self, TbpError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(TbpError::Closed);
+        };
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut bot = Self {
+            child,
+            stdin,
+            lines,
+            timeout,
+            name: String::new(),
+        };
+        match bot.receive()? {
+            BotMessage::Info { name, .. } => bot.name = name,
+            message => return Err(unexpected(message)),
+        }
+        bot.send(&FrontendMessage::Rules)?;
+        match bot.receive()? {
+            BotMessage::Ready => Ok(bot),
+            message => Err(unexpected(message)),
+        }
+    }
+
+    /// Return the name the bot gave in its info
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Send the state of the game to the bot, so it starts thinking about it
+    pub fn start(&mut self, game: &Tetris) -> Result<(), TbpError> {
+        let state = game.get_display_state();
+        let area = state.scene_area;
+        if area.x as usize != BOARD_WIDTH || area.y as usize > BOARD_HEIGHT {
+            return Err(TbpError::Protocol(format!(
+                "a {BOARD_WIDTH} column scene of at most {BOARD_HEIGHT} rows is required"
+            )));
+        }
+        let mut board = vec![vec![None; BOARD_WIDTH]; BOARD_HEIGHT];
+        for block in state.blocks {
+            board[(area.y - block.y - 1) as usize][block.x as usize] = Some('G');
+        }
+        self.send(&FrontendMessage::Start {
+            hold: game.hold_kind(),
+            queue: vec![game.player().0.kind(), game.next_kind()],
+            combo: state.stats.combo,
+            back_to_back: false,
+            board,
+        })
+    }
+
+    /// Ask the bot for its move, run it in the game and return it
+    /// (the first suggested move the player tetronimo can reach is chosen)
+    pub fn play(&mut self, game: &mut Tetris) -> Result<Move, TbpError> {
+        self.send(&FrontendMessage::Suggest)?;
+        let moves = match self.receive()? {
+            BotMessage::Suggestion { moves } => moves,
+            message => return Err(unexpected(message)),
+        };
+        let area = game.get_display_state().scene_area;
+        let placements = movegen::placements(game);
+        let chosen = moves.into_iter().find_map(|mv| {
+            let blocks = to_scene(&mv.location, area)?;
+            let t_spin = mv.spin != Spin::None;
+            placements
+                .iter()
+                .filter(|placement| placement.blocks == blocks)
+                // prefer the placement with the announced spin
+                .min_by_key(|placement| placement.t_spin != t_spin)
+                .map(|placement| (mv, placement))
+        });
+        let Some((mv, placement)) = chosen else {
+            return Err(TbpError::Protocol(
+                "no suggested move is reachable".to_owned(),
+            ));
+        };
+        for &event in &placement.events {
+            game.run(event);
+        }
+        self.send(&FrontendMessage::Play { mv })?;
+        if !game.game_over() {
+            self.send(&FrontendMessage::NewPiece {
+                piece: game.next_kind(),
+            })?;
+        }
+        Ok(mv)
+    }
+
+    /// Tell the bot the game is over (it can be started again)
+    pub fn stop(&mut self) -> Result<(), TbpError> {
+        self.send(&FrontendMessage::Stop)
+    }
+
+    /// Write a message to the bot, as a line of JSON
+    fn send(&mut self, message: &FrontendMessage) -> Result<(), TbpError> {
+        let line =
+            serde_json::to_string(message).map_err(|err| TbpError::Protocol(err.to_string()))?;
+        writeln!(self.stdin, "{line}")?;
+        Ok(self.stdin.flush()?)
+    }
+
+    /// Wait for the next message of the bot, failing on bot errors
+    fn receive(&mut self) -> Result<BotMessage, TbpError> {
+        let line = self
+            .lines
+            .recv_timeout(self.timeout)
+            .map_err(|err| match err {
+                RecvTimeoutError::Timeout => TbpError::Timeout,
+                RecvTimeoutError::Disconnected => TbpError::Closed,
+            })?;
+        match serde_json::from_str(&line) {
+            Ok(BotMessage::Error { reason }) => Err(TbpError::Rejected(reason)),
+            Ok(message) => Ok(message),
+            Err(err) => Err(TbpError::Protocol(format!(
+                "invalid message '{line}': {err}"
+            ))),
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        // ask the bot to quit, and kill it if it does not in time
+        let _ = self.send(&FrontendMessage::Quit);
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Return the error of a message received when another one was expected
+fn unexpected(message: BotMessage) -> TbpError {
+    TbpError::Protocol(format!("unexpected message {message:?}"))
+}
+
+/// Return the blocks of the scene covered by a piece location (sorted as the placements),
+/// or None if it is out of the scene
+fn to_scene(location: &PieceLocation, area: Position) -> Option<Vec<Position>> {
+    let mut blocks = location
+        .cells()
+        .into_iter()
+        .map(|(x, y)| {
+            let row = area.y as i32 - 1 - y;
+            ((0..area.x as i32).contains(&x) && (0..area.y as i32).contains(&row))
+                .then(|| Position::new(x as u8, row as u8))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    blocks.sort_by_key(|block| (block.y, block.x));
+    Some(blocks)
+}
@@ -0,0 +1,900 @@
+//! The game engine.
+
+use crate::blocks::Blocks;
+use crate::clock::Clock;
+use crate::events::{EngineEvent, Observers};
+use crate::geometry::*;
+use crate::replay::Replay;
+use crate::ruleset::Ruleset;
+use crate::statistics::Statistics;
+use crate::storage::{check_version, load_json, save_json, StorageError};
+use crate::tetronimo::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// Version of the saved game file format, bumped on every incompatible change
+const SAVE_VERSION: u32 = 3;
+
+/// Fixed time step of the engine (one frame at 60Hz)
+pub const TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+
+/// Maximum number of times moving a resting tetronimo can restart its lock delay
+const MAX_LOCK_RESETS: u8 = 15;
+
+/// Player event run by the game engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    MoveLeft,
+    MoveRight,
+    MoveDown,
+    MoveUp,
+    Rotate,
+    Drop,
+    // put the player tetronimo in the hold (once per tetronimo)
+    Hold,
+    // start holding a horizontal move (auto repeated after the DAS delay)
+    ShiftLeft,
+    ShiftRight,
+    // stop holding the horizontal move
+    ShiftStop,
+    // start holding the soft drop (gravity sped up by the soft drop factor)
+    SoftDrop,
+    // stop holding the soft drop
+    SoftDropStop,
+    // rows of garbage received from a versus opponent, full but for the hole column
+    Garbage { lines: u8, hole: u8 },
+}
+
+/// Snapshot of the game, with everything needed to display it
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisplayState {
+    pub player: Vec<Position>,
+    pub next: Vec<Position>,
+    pub projection: Vec<Position>,
+    pub blocks: Vec<Position>,
+    /// tetronimo in the hold, centered in a 4x4 area like the next one (empty if none)
+    #[serde(default)]
+    pub hold: Vec<Position>,
+    /// kind of the player tetronimo, the next one, the held one and each dropped block
+    /// (None for garbage), for the display to color them
+    #[serde(default)]
+    pub player_kind: Option<Kind>,
+    #[serde(default)]
+    pub next_kind: Option<Kind>,
+    #[serde(default)]
+    pub hold_kind: Option<Kind>,
+    /// true once the player tetronimo was swapped with the hold (until its lock)
+    #[serde(default)]
+    pub hold_used: bool,
+    #[serde(default)]
+    pub kinds: Vec<Option<Kind>>,
+    /// sides of each dropped block joined to the other blocks of its tetronimo
+    /// (see `geometry::joints`)
+    #[serde(default)]
+    pub joints: Vec<u8>,
+    pub scene_area: Position,
+    pub score: u64,
+    pub level: u32,
+    pub stats: Statistics,
+    pub game_over: bool,
+}
+
+/// The game engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tetris {
+    // total score
+    score: u64,
+    // rules of the game (size of the scene area, timings)
+    ruleset: Ruleset,
+    // player tetronimo
+    tetronimo: Tetronimo,
+    // next tetronimo to come
+    next: Tetronimo,
+    // shape of the tetronimo put aside by the player, if any
+    #[serde(default)]
+    hold: Option<Kind>,
+    // true once the hold was used for the player tetronimo (allowed once until its lock)
+    #[serde(default)]
+    hold_used: bool,
+    // stash of dropped blocks
+    dropped: Blocks,
+    // random number generator drawing the tetronimos (seeded for reproducibility)
+    rng: ChaCha12Rng,
+    // number of time steps run since the game creation
+    frame: u64,
+    // time received by the engine but not yet run as time steps
+    pending: Duration,
+    // time since the last gravity step
+    gravity_timer: Duration,
+    // time the player tetronimo has been resting on the stash
+    lock_timer: Duration,
+    // number of times the lock delay was restarted for the player tetronimo
+    lock_resets: u8,
+    // time left before gravity applies to the player tetronimo (entry delay)
+    entry_timer: Duration,
+    // horizontal move held by the player, if any, and the time since it was pressed
+    // or last repeated
+    shift: Option<Direction>,
+    shift_timer: Duration,
+    // true once the DAS delay is over, the move repeating at the ARR interval
+    #[serde(default)]
+    shift_charged: bool,
+    // true while the player holds the soft drop
+    #[serde(default)]
+    soft_drop: bool,
+    // recording of the events run in this game
+    replay: Replay,
+    // statistics of the game
+    stats: Statistics,
+    // true if the last successful movement of the player tetronimo was a rotation
+    last_rotated: bool,
+    // true if garbage pushed blocks out of the top of the scene
+    #[serde(default)]
+    topped_out: bool,
+    // subscribers of the engine events
+    #[serde(skip)]
+    observers: Observers,
+}
+
+impl Tetris {
+    /// Create a new game with a random seed
+    pub fn new(ruleset: Ruleset) -> Self {
+        Self::with_seed(ruleset, rand::thread_rng().gen())
+    }
+
+    /// Create a new game whose sequence of tetronimos is determined by the seed
+    pub fn with_seed(ruleset: Ruleset, seed: u64) -> Self {
+        let scene_area = ruleset.scene_area;
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        Self {
+            ruleset,
+            score: 0,
+            // create the first random tetronimo at the top of the area
+            tetronimo: Tetronimo::random_at_top(scene_area, &mut rng),
+            // create the next tetronimo
+            next: Tetronimo::random_at_top(scene_area, &mut rng),
+            hold: None,
+            hold_used: false,
+            // create the stash of dropped blocks at the bottom of the area
+            dropped: Blocks::new(scene_area.y),
+            rng,
+            frame: 0,
+            pending: Duration::ZERO,
+            gravity_timer: Duration::ZERO,
+            lock_timer: Duration::ZERO,
+            lock_resets: 0,
+            entry_timer: ruleset.entry_delay,
+            shift: None,
+            shift_timer: Duration::ZERO,
+            shift_charged: false,
+            soft_drop: false,
+            replay: Replay::new(seed, ruleset),
+            stats: Statistics::default(),
+            last_rotated: false,
+            topped_out: false,
+            observers: Observers::default(),
+        }
+    }
+
+    /// Start a new game, keeping the subscribers of the engine events
+    pub fn reset(&mut self) {
+        self.reset_with(self.ruleset);
+    }
+
+    /// Start a new game with other rules, keeping the subscribers of the engine events
+    pub fn reset_with(&mut self, ruleset: Ruleset) {
+        let observers = std::mem::take(&mut self.observers);
+        *self = Self::new(ruleset);
+        self.observers = observers;
+    }
+
+    /// Copy of the game for simulations, without the recording of its events
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            score: self.score,
+            ruleset: self.ruleset,
+            tetronimo: self.tetronimo.clone(),
+            next: self.next.clone(),
+            hold: self.hold,
+            hold_used: self.hold_used,
+            dropped: self.dropped.clone(),
+            rng: self.rng.clone(),
+            frame: self.frame,
+            pending: self.pending,
+            gravity_timer: self.gravity_timer,
+            lock_timer: self.lock_timer,
+            lock_resets: self.lock_resets,
+            entry_timer: self.entry_timer,
+            shift: self.shift,
+            shift_timer: self.shift_timer,
+            shift_charged: self.shift_charged,
+            soft_drop: self.soft_drop,
+            replay: Replay::new(self.replay.seed, self.ruleset),
+            stats: self.stats.clone(),
+            last_rotated: self.last_rotated,
+            topped_out: self.topped_out,
+            observers: Observers::default(),
+        }
+    }
+
+    /// Return the player tetronimo, and whether its last successful movement was a rotation
+    pub(crate) fn player(&self) -> (&Tetronimo, bool) {
+        (&self.tetronimo, self.last_rotated)
+    }
+
+    /// Return the shape of the next tetronimo
+    pub(crate) fn next_kind(&self) -> Kind {
+        self.next.kind()
+    }
+
+    /// Return the shape of the held tetronimo, if any
+    pub(crate) fn hold_kind(&self) -> Option<Kind> {
+        self.hold
+    }
+
+    /// Subscribe to the engine events, returning the channel receiving them
+    pub fn subscribe(&mut self) -> Receiver<EngineEvent> {
+        self.observers.subscribe()
+    }
+
+    /// Return the recording of all events run since the game creation
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Return the number of time steps run since the game creation
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Return the game time, i.e. the duration of the time steps run
+    pub fn clock(&self) -> Duration {
+        Duration::from_nanos(TIMESTEP.as_nanos() as u64 * self.frame)
+    }
+
+    /// Return true if the player tetronimo has no more room to move
+    pub fn game_over(&self) -> bool {
+        self.topped_out || self.get_distance_to_drop() == 0
+    }
+
+    /// Return the player level based on the current score
+    pub fn level(&self) -> u32 {
+        (self.score / 100) as u32
+    }
+
+    /// Write the full state of the game to a file
+    pub fn save(&self, path: &std::path::Path) -> Result<(), StorageError> {
+        save_json(
+            path,
+            &SavedGame {
+                version: SAVE_VERSION,
+                game: self.clone(),
+            },
+        )
+    }
+
+    /// Read a game previously saved to a file, ready to be resumed
+    pub fn load(path: &std::path::Path) -> Result<Self, StorageError> {
+        let saved: SavedGame = load_json(path)?;
+        check_version(saved.version, SAVE_VERSION)?;
+        Ok(saved.game)
+    }
+
+    /// Run a player event (ignored once the game is over)
+    pub fn run(&mut self, event: Event) {
+        if self.game_over() {
+            return;
+        }
+        // record the event along with the current frame
+        self.replay.record(self.frame, event);
+        if !matches!(
+            event,
+            Event::ShiftStop | Event::SoftDropStop | Event::Garbage { .. }
+        ) {
+            self.stats.record_input();
+        }
+        let direction = match event {
+            Event::MoveLeft => Direction::Left,
+            Event::MoveRight => Direction::Right,
+            Event::MoveUp => Direction::Up,
+            Event::MoveDown => Direction::Down,
+            Event::Rotate => Direction::Rotate,
+            Event::Drop => return self.drop(),
+            Event::Hold => return self.hold(),
+            Event::ShiftLeft => return self.start_shift(Direction::Left),
+            Event::ShiftRight => return self.start_shift(Direction::Right),
+            Event::ShiftStop => {
+                self.shift = None;
+                return;
+            }
+            Event::SoftDrop => {
+                // move once, then fall at the soft drop speed
+                self.soft_drop = true;
+                Direction::Down
+            }
+            Event::SoftDropStop => {
+                self.soft_drop = false;
+                return;
+            }
+            Event::Garbage { lines, hole } => return self.add_garbage(lines, hole),
+        };
+        if self.move_tetronimo(direction) && direction == Direction::Rotate {
+            self.cut_das();
+        }
+    }
+
+    /// Advance the game by the time elapsed, run as fixed time steps
+    /// (any remainder is kept for the next call)
+    pub fn tick(&mut self, elapsed: Duration) {
+        self.pending += elapsed;
+        while self.pending >= TIMESTEP {
+            self.pending -= TIMESTEP;
+            self.step();
+        }
+    }
+
+    /// Advance the game by the time elapsed on a clock
+    pub fn advance(&mut self, clock: &mut impl Clock) {
+        self.tick(clock.elapsed());
+    }
+
+    /// Return the Display state, i.e. the position of all blocks along
+    /// with the remainder of data/stats needed to display the game
+    pub fn get_display_state(&self) -> DisplayState {
+        DisplayState {
+            // Get the collection of blocks representing the player tetronimo
+            player: self.tetronimo.now().collect(),
+            // Get the collection of blocks representing the next tetronimo
+            // transform it to center in a 4x4 area ('next' drawing panel area)
+            next: self
+                .next
+                .transform(Direction::Rotate, 0, Position::new(4, 4))
+                .now()
+                .collect(),
+            hold: self
+                .hold
+                .map(|kind| {
+                    Tetronimo::at_top(kind, self.ruleset.scene_area)
+                        .transform(Direction::Rotate, 0, Position::new(4, 4))
+                        .now()
+                        .collect()
+                })
+                .unwrap_or_default(),
+            // projection of the player tetronimo at the top of the stack
+            projection: self.build_projection().now().collect(),
+            // all the blocks in the dropped stack
+            blocks: self.dropped.get_blocks(),
+            player_kind: Some(self.tetronimo.kind()),
+            next_kind: Some(self.next.kind()),
+            hold_kind: self.hold,
+            hold_used: self.hold_used,
+            kinds: self.dropped.get_kinds(),
+            joints: self.dropped.get_joints(),
+            scene_area: self.ruleset.scene_area,
+            score: self.score,
+            level: self.level(),
+            stats: self.stats.clone(),
+            game_over: self.game_over(),
+        }
+    }
+
+    /// Return the distance (in blocks) from the player tetronimo to the top of the stash dropped blocks
+    fn get_distance_to_drop(&self) -> u8 {
+        self.dropped.distance_to(&self.tetronimo)
+    }
+
+    /// Return the time interval between gravity steps based on the player level
+    fn calculate_interval(&self) -> Duration {
+        let base_timer = 1500;
+        Duration::from_millis(base_timer / (self.level() as u64 + 3))
+    }
+
+    /// Run a single time step: auto shift, gravity and lock delay
+    fn step(&mut self) {
+        if self.game_over() {
+            return;
+        }
+        self.frame += 1;
+        self.replay.frames = self.frame;
+        self.stats.elapsed = self.clock();
+
+        // auto repeat the held horizontal move, once the DAS delay is over
+        if let Some(direction) = self.shift {
+            self.shift_timer += TIMESTEP;
+            loop {
+                let delay = match self.shift_charged {
+                    true => self.ruleset.arr,
+                    false => self.ruleset.das,
+                };
+                if self.shift_timer < delay || !self.move_tetronimo(direction) {
+                    break;
+                }
+                self.shift_timer -= delay;
+                self.shift_charged = true;
+            }
+        }
+
+        // gravity only applies after the entry delay of the tetronimo
+        if !self.entry_timer.is_zero() {
+            self.entry_timer = self.entry_timer.saturating_sub(TIMESTEP);
+            return;
+        }
+        self.gravity_timer += TIMESTEP;
+        let interval = match self.soft_drop {
+            true => self.calculate_interval() / self.ruleset.soft_drop_factor.max(1),
+            false => self.calculate_interval(),
+        };
+        if self.gravity_timer >= interval {
+            self.gravity_timer = Duration::ZERO;
+            // a soft drop faster than the time step moves several rows at once
+            let rows = match self.soft_drop {
+                true => (TIMESTEP.as_nanos() / interval.as_nanos().max(1)).max(1),
+                false => 1,
+            };
+            for _ in 0..rows {
+                if !self.move_tetronimo(Direction::Down) {
+                    break;
+                }
+            }
+        }
+
+        // lock the tetronimo once it rested on the stash for the lock delay
+        if self.get_distance_to_drop() == 1 {
+            self.lock_timer += TIMESTEP;
+            if self.lock_timer >= self.ruleset.lock_delay {
+                self.lock_tetronimo();
+            }
+        } else {
+            self.lock_timer = Duration::ZERO;
+        }
+    }
+
+    /// Insert rows of garbage under the stash, lifting the player tetronimo above them
+    fn add_garbage(&mut self, lines: u8, hole: u8) {
+        let width = self.ruleset.scene_area.x;
+        self.topped_out |= self.dropped.add_garbage(lines, hole.min(width - 1), width);
+        while self.get_distance_to_drop() == 0 {
+            let lifted = self
+                .tetronimo
+                .transform(Direction::Up, 1, self.ruleset.scene_area);
+            if lifted.now().eq(self.tetronimo.now()) {
+                break;
+            }
+            self.tetronimo = lifted;
+        }
+        if self.game_over() {
+            self.observers
+                .notify(EngineEvent::GameOver { score: self.score });
+        }
+    }
+
+    /// Pause the auto repeat of the held horizontal move for the DAS cut delay
+    /// (after a rotation or the spawn of a tetronimo)
+    fn cut_das(&mut self) {
+        if self.shift.is_some() {
+            let cut = self.ruleset.das.saturating_sub(self.ruleset.dcd);
+            self.shift_timer = match self.shift_charged {
+                true => cut,
+                false => self.shift_timer.min(cut),
+            };
+            self.shift_charged = false;
+        }
+    }
+
+    /// Start holding a horizontal move: move once, then wait for the DAS delay
+    fn start_shift(&mut self, direction: Direction) {
+        self.move_tetronimo(direction);
+        self.shift = Some(direction);
+        self.shift_timer = Duration::ZERO;
+        self.shift_charged = false;
+    }
+
+    /// Move the player tetronimo in a given direction, returning true if it moved
+    fn move_tetronimo(&mut self, direction: Direction) -> bool {
+        // update the tetronimo with its transformed instance
+        let moved_tetronimo = self
+            .tetronimo
+            .transform(direction, 1, self.ruleset.scene_area);
+        // Check if the new tetronimo collides with the stack of dropped
+        // reject the new tetronimo if the movement is invalid
+        if self.dropped.distance_to(&moved_tetronimo) == 0
+            || moved_tetronimo.now().eq(self.tetronimo.now())
+        {
+            return false;
+        }
+        self.last_rotated = direction == Direction::Rotate;
+        self.tetronimo = moved_tetronimo;
+        // moving a resting tetronimo restarts its lock delay (a limited number of times)
+        if !self.lock_timer.is_zero() && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_timer = Duration::ZERO;
+            self.lock_resets += 1;
+        }
+        true
+    }
+
+    /// Add the player tetronimo to the stash of dropped blocks, clear the completed rows
+    /// and bring the next tetronimo into play
+    fn lock_tetronimo(&mut self) {
+        let kind = self.tetronimo.kind();
+        let t_spin = self.is_t_spin();
+        let level = self.level();
+        self.dropped.add(&self.tetronimo);
+        self.observers.notify(EngineEvent::PieceLocked {
+            kind,
+            blocks: self.tetronimo.now().collect(),
+            t_spin,
+        });
+        // update the score, according to the removed/cleared lines
+        let cleared_rows = self.dropped.clear_completed_rows(self.ruleset.scene_area.x);
+        self.score += 2u64.pow(cleared_rows.len() as u32) - 1;
+        self.stats
+            .record_lock(kind, cleared_rows.len() as u8, t_spin);
+        if !cleared_rows.is_empty() {
+            self.observers.notify(EngineEvent::LinesCleared {
+                rows: cleared_rows,
+                t_spin,
+                combo: self.stats.combo,
+                perfect_clear: self.dropped.is_empty(),
+            });
+        }
+        if self.level() > level {
+            self.observers.notify(EngineEvent::LevelUp {
+                level: self.level(),
+            });
+        }
+        let next = self.take_next();
+        self.hold_used = false;
+        self.spawn(next);
+    }
+
+    /// Put the player tetronimo in the hold, bringing the held one (or else the next one)
+    /// into play, at most once per tetronimo
+    fn hold(&mut self) {
+        if self.hold_used {
+            return;
+        }
+        let kind = self.tetronimo.kind();
+        let tetronimo = match self.hold {
+            Some(held) => Tetronimo::at_top(held, self.ruleset.scene_area),
+            None => self.take_next(),
+        };
+        self.hold = Some(kind);
+        self.hold_used = true;
+        self.observers.notify(EngineEvent::HoldUsed { kind });
+        self.spawn(tetronimo);
+    }
+
+    /// Take the next tetronimo (already instantiated) and create a new one in its place
+    fn take_next(&mut self) -> Tetronimo {
+        let next = Tetronimo::random_at_top(self.ruleset.scene_area, &mut self.rng);
+        std::mem::replace(&mut self.next, next)
+    }
+
+    /// Bring a tetronimo into play at the top of the scene, restarting its delays
+    fn spawn(&mut self, tetronimo: Tetronimo) {
+        self.tetronimo = tetronimo;
+        self.last_rotated = false;
+        self.gravity_timer = Duration::ZERO;
+        self.lock_timer = Duration::ZERO;
+        self.lock_resets = 0;
+        self.entry_timer = self.ruleset.entry_delay;
+        self.cut_das();
+        if self.game_over() {
+            self.observers
+                .notify(EngineEvent::GameOver { score: self.score });
+        }
+    }
+
+    /// Causes the player tetronimo to drop in the stash of dropped blocks
+    fn drop(&mut self) {
+        // dropping from above the projection cancels any preceding rotation
+        if self.get_distance_to_drop() > 1 {
+            self.last_rotated = false;
+        }
+        // update the tetronimo with the projection,
+        // i.e, distance to dropped stash is equal to 1
+        self.observers.notify(EngineEvent::HardDropped {
+            kind: self.tetronimo.kind(),
+            blocks: self.tetronimo.now().collect(),
+            rows: self.get_distance_to_drop().saturating_sub(1),
+        });
+        self.tetronimo = self.build_projection();
+        // add it to the the stash of dropped
+        self.lock_tetronimo();
+    }
+
+    /// Return true if the player tetronimo is a T moved in place by a rotation,
+    /// with at least three of the four corners around its center occupied
+    fn is_t_spin(&self) -> bool {
+        if self.tetronimo.kind() != Kind::T || !self.last_rotated {
+            return false;
+        }
+        let blocks: Vec<Position> = self.tetronimo.now().collect();
+        // the center of the T is the block adjacent to all the others
+        let Some(center) = blocks.iter().find(|block| {
+            blocks
+                .iter()
+                .filter(|other| block.x.abs_diff(other.x) + block.y.abs_diff(other.y) == 1)
+                .count()
+                == 3
+        }) else {
+            return false;
+        };
+        // corners out of the side/bottom limits of the scene count as occupied
+        [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .into_iter()
+            .map(|(dx, dy)| (center.x as i16 + dx, center.y as i16 + dy))
+            .filter(|&(x, y)| {
+                x < 0
+                    || x >= self.ruleset.scene_area.x as i16
+                    || y >= self.ruleset.scene_area.y as i16
+                    || (y >= 0 && self.dropped.is_occupied(Position::new(x as u8, y as u8)))
+            })
+            .count()
+            >= 3
+    }
+
+    /// Returns the projection of the player tetronimo on the stash of dropped blocks
+    fn build_projection(&self) -> Tetronimo {
+        self.tetronimo.transform(
+            Direction::Down,
+            self.get_distance_to_drop().saturating_sub(1),
+            self.ruleset.scene_area,
+        )
+    }
+}
+
+/// Content of a saved game file
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    version: u32,
+    game: Tetris,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::EngineEvent,
+        ruleset::Ruleset,
+        tetris::{Event, Tetris, TIMESTEP},
+        tetronimo::Tetronimo,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn save_and_resume_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 11);
+        for _ in 0..5 {
+            game.run(Event::MoveLeft);
+            game.run(Event::Drop);
+            game.tick(Duration::from_millis(250));
+        }
+        let path = std::env::temp_dir().join("tetris_savegame_test.json");
+        game.save(&path).unwrap();
+        let mut resumed = Tetris::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(game.get_display_state(), resumed.get_display_state());
+
+        // the restored random generator draws the same tetronimos as the original one
+        for _ in 0..10 {
+            game.run(Event::Drop);
+            resumed.run(Event::Drop);
+            game.tick(Duration::from_millis(250));
+            resumed.tick(Duration::from_millis(250));
+            assert_eq!(game.get_display_state(), resumed.get_display_state());
+        }
+    }
+
+    #[test]
+    fn gravity_and_lock_delay_test() {
+        let ruleset = Ruleset::default();
+        let mut game = Tetris::with_seed(ruleset, 1);
+        let top = game.get_display_state().player;
+
+        // nothing falls during the entry delay
+        game.tick(ruleset.entry_delay);
+        assert_eq!(game.get_display_state().player, top);
+
+        // then the tetronimo falls one row per gravity interval (500ms at level 0)
+        game.tick(Duration::from_millis(480));
+        assert_eq!(game.get_display_state().player, top);
+        game.tick(Duration::from_millis(40));
+        let fallen = game.get_display_state().player;
+        assert!(fallen.iter().zip(&top).all(|(f, t)| f.y == t.y + 1));
+
+        // once resting on the floor, it locks after the lock delay
+        while game.get_display_state().player != game.get_display_state().projection {
+            game.tick(Duration::from_millis(100));
+        }
+        assert_eq!(game.get_display_state().stats.pieces, 0);
+        game.tick(ruleset.lock_delay);
+        assert_eq!(game.get_display_state().stats.pieces, 1);
+        assert_eq!(game.get_display_state().stats.elapsed, game.clock());
+    }
+
+    #[test]
+    fn auto_shift_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 2);
+        let column = |game: &Tetris| game.get_display_state().player.iter().map(|p| p.x).min();
+        let start = column(&game).unwrap();
+
+        // one move on press, then repeated moves after the DAS delay until the wall
+        game.run(Event::ShiftLeft);
+        assert_eq!(column(&game), Some(start - 1));
+        game.tick(Duration::from_millis(150));
+        assert_eq!(column(&game), Some(start - 1));
+        game.tick(Duration::from_millis(100));
+        assert!(column(&game) < Some(start - 1));
+        game.tick(Duration::from_millis(200));
+        assert_eq!(column(&game), Some(0));
+
+        // released, the tetronimo stays in place
+        game.run(Event::ShiftStop);
+        game.run(Event::MoveRight);
+        game.tick(Duration::from_millis(300));
+        assert_eq!(column(&game), Some(1));
+    }
+
+    #[test]
+    fn slow_auto_repeat_test() {
+        // an ARR longer than the DAS delay repeats the move at the ARR interval
+        let ruleset = Ruleset {
+            das: Duration::from_millis(50),
+            arr: Duration::from_millis(100),
+            ..Ruleset::default()
+        };
+        let mut game = Tetris::with_seed(ruleset, 2);
+        let column = |game: &Tetris| game.get_display_state().player.iter().map(|p| p.x).min();
+        let start = column(&game).unwrap();
+        game.run(Event::ShiftLeft);
+        game.tick(Duration::from_millis(60));
+        assert_eq!(column(&game), Some(start - 2));
+        game.tick(Duration::from_millis(60));
+        assert_eq!(column(&game), Some(start - 2));
+        game.tick(Duration::from_millis(60));
+        assert_eq!(column(&game), Some(start - 3));
+    }
+
+    #[test]
+    fn handling_test() {
+        let ruleset = Ruleset {
+            arr: Duration::ZERO,
+            dcd: Duration::from_millis(100),
+            ..Ruleset::default()
+        };
+        let mut game = Tetris::with_seed(ruleset, 2);
+        let column = |game: &Tetris| game.get_display_state().player.iter().map(|p| p.x).min();
+        let row = |game: &Tetris| game.get_display_state().player.iter().map(|p| p.y).min();
+
+        // with no ARR, the held move reaches the wall as soon as the DAS delay is over
+        game.run(Event::ShiftLeft);
+        game.tick(ruleset.das + TIMESTEP);
+        assert_eq!(column(&game), Some(0));
+
+        // the spawn of the next tetronimo pauses the auto repeat for the DAS cut delay
+        game.run(Event::Drop);
+        let spawned = column(&game);
+        game.tick(ruleset.dcd / 2);
+        assert_eq!(column(&game), spawned);
+        game.tick(ruleset.dcd);
+        assert_eq!(column(&game), Some(0));
+        game.run(Event::ShiftStop);
+
+        // the soft drop falls faster than the gravity, until released
+        let start = row(&game).unwrap();
+        game.run(Event::SoftDrop);
+        assert_eq!(row(&game), Some(start + 1));
+        game.tick(ruleset.entry_delay + Duration::from_millis(100));
+        let dropped = row(&game).unwrap();
+        assert!(dropped > start + 3);
+        game.run(Event::SoftDropStop);
+        game.tick(Duration::from_millis(100));
+        assert!(row(&game).unwrap() <= dropped + 1);
+
+        // with no DAS delay and an ARR, the move repeats at once then at the ARR interval
+        let ruleset = Ruleset {
+            das: Duration::ZERO,
+            arr: Duration::from_millis(33),
+            ..ruleset
+        };
+        let mut game = Tetris::with_seed(ruleset, 2);
+        let start = column(&game).unwrap();
+        game.run(Event::ShiftLeft);
+        game.tick(TIMESTEP);
+        assert_eq!(column(&game), Some(start - 2));
+        game.tick(ruleset.arr);
+        assert_eq!(column(&game), Some(start - 3));
+    }
+
+    #[test]
+    fn block_kinds_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 3);
+        let kind = game.get_display_state().player_kind;
+        game.run(Event::Drop);
+        game.run(Event::Garbage { lines: 2, hole: 0 });
+        let state = game.get_display_state();
+        assert_eq!(state.kinds.len(), state.blocks.len());
+
+        // the garbage rows at the bottom have no kind, the dropped tetronimo keeps its own
+        assert!(state.kinds[..2 * 9].iter().all(Option::is_none));
+        assert!(state.kinds[2 * 9..].iter().all(|&block| block == kind));
+
+        // each block of the tetronimo is joined to another one, the garbage blocks are not
+        assert!(state.joints[..2 * 9].iter().all(|&joints| joints == 0));
+        assert!(state.joints[2 * 9..].iter().all(|&joints| joints != 0));
+    }
+
+    #[test]
+    fn hold_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 2);
+        let events = game.subscribe();
+        let first = game.player().0.kind();
+        let second = game.next_kind();
+
+        // the first hold takes the next tetronimo
+        game.run(Event::MoveLeft);
+        game.run(Event::Hold);
+        let state = game.get_display_state();
+        assert_eq!(state.hold_kind, Some(first));
+        assert_eq!(state.hold.len(), 4);
+        assert_eq!(state.player_kind, Some(second));
+        assert_ne!(state.next_kind, None);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![EngineEvent::HoldUsed { kind: first }]
+        );
+
+        // the hold is used once per tetronimo
+        game.run(Event::Hold);
+        assert_eq!(game.player().0.kind(), second);
+        assert_eq!(game.hold_kind(), Some(first));
+
+        // after a lock, the hold swaps with the player tetronimo, back at the top
+        game.run(Event::Drop);
+        let third = game.player().0.kind();
+        game.run(Event::Hold);
+        assert_eq!(game.player().0.kind(), first);
+        assert_eq!(game.hold_kind(), Some(third));
+        game.run(Event::Hold);
+        game.run(Event::Drop);
+        game.run(Event::Hold);
+        let area = game.get_display_state().scene_area;
+        assert_eq!(game.player().0, &Tetronimo::at_top(third, area));
+    }
+
+    #[test]
+    fn engine_events_test() {
+        let mut game = Tetris::with_seed(Ruleset::default(), 5);
+        let events = game.subscribe();
+        while !game.game_over() {
+            game.run(Event::Drop);
+        }
+        let events: Vec<EngineEvent> = events.try_iter().collect();
+        let locked = events
+            .iter()
+            .filter(|event| matches!(event, EngineEvent::PieceLocked { .. }))
+            .count();
+        assert_eq!(locked as u32, game.get_display_state().stats.pieces);
+        // every piece was hard dropped before its lock
+        let dropped = events
+            .iter()
+            .filter(|event| matches!(event, EngineEvent::HardDropped { .. }))
+            .count();
+        assert_eq!(dropped, locked);
+        assert!(matches!(events[0], EngineEvent::HardDropped { rows, .. } if rows > 0));
+        assert_eq!(
+            events.last(),
+            Some(&EngineEvent::GameOver { score: game.score })
+        );
+
+        // a copy of the engine does not notify the subscribers of the original
+        let mut copy = game.clone();
+        let events = game.subscribe();
+        copy.reset();
+        copy.run(Event::Drop);
+        assert!(events.try_recv().is_err());
+    }
+}
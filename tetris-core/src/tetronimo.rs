@@ -1,26 +1,73 @@
+//! Tetronimos: their shapes, movements and rotations.
+
 use crate::geometry::{Direction, Position};
 use lazy_static::lazy_static;
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
 
-#[derive(Debug, Clone)]
+/// The seven tetronimo shapes, in the same order as their prototypes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    I,
+    S,
+    Z,
+    O,
+    J,
+    L,
+    T,
+}
+
+impl Kind {
+    /// All the kinds, in the order of their prototypes
+    pub const ALL: [Kind; 7] = [
+        Kind::I,
+        Kind::S,
+        Kind::Z,
+        Kind::O,
+        Kind::J,
+        Kind::L,
+        Kind::T,
+    ];
+}
+
+/// A tetronimo placed in the scene
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Tetronimo {
     position: Position,
     prototype: TetronimoPrototype,
+    kind: Kind,
 }
 
 impl Tetronimo {
-    /// Returns a tetronimo positioned at the top middle of the scene_area
-    pub fn random_at_top(scene_area: Position) -> Self {
-        let rng = &mut rand::thread_rng();
-        let prototype =
-            PROTOTYPES[rng.next_u32() as usize % PROTOTYPES.len()].rotate(rng.next_u32() as i8 % 4);
+    /// Returns a tetronimo positioned at the top middle of the scene_area,
+    /// drawn from the given random number generator
+    pub fn random_at_top(scene_area: Position, rng: &mut impl RngCore) -> Self {
+        let index = rng.next_u32() as usize % PROTOTYPES.len();
+        let prototype = PROTOTYPES[index].rotate(rng.next_u32() as i8 % 4);
+        Self {
+            position: Tetronimo::repostion(&prototype, scene_area.top_middle(), scene_area),
+            prototype,
+            kind: Kind::ALL[index],
+        }
+    }
+
+    /// Returns a tetronimo of the given shape, unrotated, positioned at the top middle
+    /// of the scene_area
+    pub fn at_top(kind: Kind, scene_area: Position) -> Self {
+        let prototype = PROTOTYPES[kind as usize].clone();
         Self {
             position: Tetronimo::repostion(&prototype, scene_area.top_middle(), scene_area),
             prototype,
+            kind,
         }
     }
 
+    /// Returns the shape of the tetronimo
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
     /// Returns a transformed version of the tetronimo
     pub fn transform(&self, direction: Direction, step: u8, scene_area: Position) -> Self {
         let mut next_prot = self.prototype.clone();
@@ -37,11 +84,12 @@ impl Tetronimo {
         Self {
             position: Tetronimo::repostion(&next_prot, next_pos, scene_area),
             prototype: next_prot,
+            kind: self.kind,
         }
     }
 
     /// Returns an iterator over the blocks of the tetronimo at the current position
-    pub fn now(&self) -> impl Iterator<Item = Position>{
+    pub fn now(&self) -> impl Iterator<Item = Position> {
         self.prototype
             .blocks
             .map(|block| Position {
@@ -100,7 +148,7 @@ lazy_static! {
 
 type PrototypeBlocks = [(i8, i8); 4];
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct TetronimoPrototype {
     blocks: PrototypeBlocks,
     limits: ProtoTypeLimits,
@@ -128,13 +176,13 @@ const fn rotate_block_position(cell: (i8, i8), step: i8) -> (i8, i8) {
 }
 
 const fn turn(rotation: i8) -> i8 {
-    (1 - (rotation & 1) as i8) * (1 - (rotation & 2) as i8)
+    (1 - (rotation & 1)) * (1 - (rotation & 2))
 }
 const fn shift(rotation: i8) -> i8 {
-    ((rotation as i8) & 2) >> 1
+    (rotation & 2) >> 1
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct ProtoTypeLimits {
     left: i8,
     right: i8,
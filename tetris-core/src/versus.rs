@@ -0,0 +1,256 @@
+//! Two-player versus games: lines cleared by a player are sent as garbage to the opponent.
+
+use crate::clock::Clock;
+use crate::events::EngineEvent;
+use crate::ruleset::Ruleset;
+use crate::tetris::{Event, Tetris};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// Garbage lines sent for clearing 0 to 4 lines
+const LINES_ATTACK: [u32; 5] = [0, 0, 1, 2, 4];
+/// Garbage lines sent for clearing 0 to 3 lines with a T-spin
+const T_SPIN_ATTACK: [u32; 4] = [0, 2, 4, 6];
+/// Extra garbage lines sent by consecutive clears (from the second one, the last value repeats)
+const COMBO_ATTACK: [u32; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Return the garbage lines sent by a clear, following the standard attack table
+/// (`combo` counts the consecutive pieces clearing lines, this one included)
+pub fn attack(lines: u32, t_spin: bool, combo: u32, back_to_back: bool) -> u32 {
+    let base = match t_spin {
+        true => T_SPIN_ATTACK[lines.min(3) as usize],
+        false => LINES_ATTACK[lines.min(4) as usize],
+    };
+    let combo = COMBO_ATTACK[(combo.saturating_sub(1) as usize).min(COMBO_ATTACK.len() - 1)];
+    base + combo + back_to_back as u32
+}
+
+/// One side of a versus game
+struct Side {
+    engine: Tetris,
+    // channel receiving the events of the engine
+    events: Receiver<EngineEvent>,
+    // garbage lines received from the opponent, not yet added to the stash
+    incoming: u32,
+    // total garbage lines sent to the opponent (after cancellation)
+    sent: u32,
+    // true if the last clear was a tetris or a T-spin (the next one gets a bonus)
+    back_to_back: bool,
+}
+
+/// Versus game between two players, playing the same sequence of tetronimos
+/// (cloned without the replays of the engines, to be snapshotted at every frame)
+#[derive(Clone)]
+pub struct Versus {
+    sides: [Side; 2],
+    // random number generator of the garbage holes
+    rng: ChaCha12Rng,
+}
+
+impl Versus {
+    /// Create a versus game, both players getting the tetronimos determined by the seed
+    pub fn new(ruleset: Ruleset, seed: u64) -> Self {
+        let side = || {
+            let mut engine = Tetris::with_seed(ruleset, seed);
+            Side {
+                events: engine.subscribe(),
+                engine,
+                incoming: 0,
+                sent: 0,
+                back_to_back: false,
+            }
+        };
+        Self {
+            sides: [side(), side()],
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Return the game engine of a player (0 or 1)
+    pub fn engine(&self, player: usize) -> &Tetris {
+        &self.sides[player].engine
+    }
+
+    /// Return the garbage lines waiting to be added to the stash of a player
+    pub fn incoming(&self, player: usize) -> u32 {
+        self.sides[player].incoming
+    }
+
+    /// Return the total garbage lines a player has sent to the opponent
+    pub fn sent(&self, player: usize) -> u32 {
+        self.sides[player].sent
+    }
+
+    /// Return true once a player has topped out
+    pub fn is_over(&self) -> bool {
+        self.sides.iter().any(|side| side.engine.game_over())
+    }
+
+    /// Return the player still standing when the other one has topped out
+    pub fn winner(&self) -> Option<usize> {
+        match (
+            self.sides[0].engine.game_over(),
+            self.sides[1].engine.game_over(),
+        ) {
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Run a player event (ignored once the game is over)
+    pub fn run(&mut self, player: usize, event: Event) {
+        if self.is_over() {
+            return;
+        }
+        self.sides[player].engine.run(event);
+        self.exchange();
+    }
+
+    /// Advance both games by the time elapsed (stopped once the game is over)
+    pub fn tick(&mut self, elapsed: Duration) {
+        if self.is_over() {
+            return;
+        }
+        for side in &mut self.sides {
+            side.engine.tick(elapsed);
+        }
+        self.exchange();
+    }
+
+    /// Advance both games by the time elapsed since the last call
+    pub fn advance(&mut self, clock: &mut impl Clock) {
+        self.tick(clock.elapsed());
+    }
+
+    /// Exchange the garbage of the clears and locks that happened in both games
+    fn exchange(&mut self) {
+        for player in 0..2 {
+            let events: Vec<_> = self.sides[player].events.try_iter().collect();
+            self.resolve(player, &events);
+        }
+    }
+
+    /// Turn the clears of a player into garbage for the opponent, cancelling the incoming
+    /// garbage first, and add the incoming garbage to the stash on locks without clears
+    fn resolve(&mut self, player: usize, events: &[EngineEvent]) {
+        let mut locked = false;
+        let mut cleared = false;
+        for event in events {
+            match event {
+                EngineEvent::PieceLocked { .. } => locked = true,
+                EngineEvent::LinesCleared {
+                    rows,
+                    t_spin,
+                    combo,
+                    ..
+                } => {
+                    cleared = true;
+                    let side = &mut self.sides[player];
+                    let difficult = *t_spin || rows.len() == 4;
+                    let mut lines = attack(
+                        rows.len() as u32,
+                        *t_spin,
+                        *combo,
+                        difficult && side.back_to_back,
+                    );
+                    side.back_to_back = difficult;
+                    let cancelled = lines.min(side.incoming);
+                    side.incoming -= cancelled;
+                    lines -= cancelled;
+                    side.sent += lines;
+                    self.sides[1 - player].incoming += lines;
+                }
+                _ => {}
+            }
+        }
+        let side = &mut self.sides[player];
+        if locked && !cleared && side.incoming > 0 {
+            let lines = side.incoming.min(u8::MAX as u32) as u8;
+            let hole = self
+                .rng
+                .gen_range(0..side.engine.get_display_state().scene_area.x);
+            side.incoming -= lines as u32;
+            side.engine.run(Event::Garbage { lines, hole });
+        }
+    }
+}
+
+// engines are forked, their events already exchanged
+impl Clone for Side {
+    fn clone(&self) -> Self {
+        let mut engine = self.engine.fork();
+        Self {
+            events: engine.subscribe(),
+            engine,
+            incoming: self.incoming,
+            sent: self.sent,
+            back_to_back: self.back_to_back,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::EngineEvent,
+        ruleset::Ruleset,
+        tetris::Event,
+        tetronimo::Kind,
+        versus::{attack, Versus},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn attack_test() {
+        assert_eq!(attack(1, false, 1, false), 0);
+        assert_eq!(attack(4, false, 1, false), 4);
+        assert_eq!(attack(4, false, 1, true), 5);
+        assert_eq!(attack(2, true, 1, false), 4);
+        assert_eq!(attack(2, false, 3, false), 2);
+        assert_eq!(attack(1, false, 40, false), 5);
+    }
+
+    #[test]
+    fn garbage_exchange_test() {
+        let mut versus = Versus::new(Ruleset::default(), 1);
+        let cleared = |lines: usize, combo| EngineEvent::LinesCleared {
+            rows: (0..lines as u8).collect(),
+            t_spin: false,
+            combo,
+            perfect_clear: false,
+        };
+        let locked = EngineEvent::PieceLocked {
+            kind: Kind::I,
+            blocks: Vec::new(),
+            t_spin: false,
+        };
+
+        // a tetris sends 4 lines, then a back to back tetris in a combo sends 6,
+        // cancelling the 3 incoming lines first
+        versus.resolve(0, &[locked.clone(), cleared(4, 1)]);
+        assert_eq!((versus.incoming(0), versus.incoming(1)), (0, 4));
+        versus.sides[0].incoming = 3;
+        versus.resolve(0, &[locked.clone(), cleared(4, 2)]);
+        assert_eq!((versus.incoming(0), versus.incoming(1)), (0, 4 + 3));
+        assert_eq!(versus.sent(0), 4 + 3);
+
+        // a lock without clear adds the incoming garbage to the stash
+        versus.resolve(1, &[locked]);
+        assert_eq!(versus.incoming(1), 0);
+        let state = versus.engine(1).get_display_state();
+        assert_eq!(state.blocks.len(), 7 * 9);
+
+        // topping out declares the winner and stops the game
+        for _ in 0..20 {
+            versus.run(1, Event::Drop);
+        }
+        assert!(versus.is_over());
+        assert_eq!(versus.winner(), Some(0));
+        let frame = versus.engine(0).frame();
+        versus.tick(Duration::from_secs(1));
+        assert_eq!(versus.engine(0).frame(), frame);
+    }
+}
@@ -0,0 +1,155 @@
+use std::net::TcpListener;
+use std::time::Duration;
+use tetris_core::{
+    geometry::Position,
+    net::{NetError, NetVersus},
+    rollback::{LoopbackTransport, RollbackVersus, TcpTransport, MAX_ROLLBACK},
+    ruleset::Ruleset,
+    tetris::{Event, TIMESTEP},
+    versus::Versus,
+};
+
+const SEED: u64 = 9;
+const FRAMES: u64 = 900;
+
+/// Scripted inputs of a player for a frame
+fn script(player: usize, frame: u64) -> Vec<Event> {
+    let mut events = Vec::new();
+    if frame.is_multiple_of(13) {
+        events.push([Event::MoveLeft, Event::MoveRight][player]);
+    }
+    if frame % 17 == 5 + player as u64 {
+        events.push(Event::Rotate);
+    }
+    if frame % 40 == 20 + 5 * player as u64 {
+        events.push(Event::Drop);
+    }
+    events
+}
+
+/// Connect two clients over a loopback transport
+fn connect(latency: u32, jitter: u32) -> [RollbackVersus<LoopbackTransport>; 2] {
+    let (first, second) = LoopbackTransport::pair(latency, jitter, 1);
+    [
+        RollbackVersus::new(Ruleset::default(), SEED, 0, first),
+        RollbackVersus::new(Ruleset::default(), SEED, 1, second),
+    ]
+}
+
+/// Let the clients exchange their last inputs, without running new frames
+fn settle(clients: &mut [RollbackVersus<LoopbackTransport>; 2]) {
+    while clients
+        .iter()
+        .any(|client| client.confirmed_frame() < client.frame() || client.frame() < FRAMES)
+    {
+        for client in clients.iter_mut() {
+            client.tick(Duration::ZERO).unwrap();
+        }
+    }
+}
+
+/// Assert both versus games are the same
+fn assert_same(first: &Versus, second: &Versus) {
+    for player in 0..2 {
+        assert_eq!(
+            first.engine(player).get_display_state(),
+            second.engine(player).get_display_state()
+        );
+        assert_eq!(first.incoming(player), second.incoming(player));
+    }
+}
+
+#[test]
+fn rollback_matches_local_game_test() {
+    // latency and jitter below the rollback window: each tick runs one frame
+    let mut clients = connect(2, 4);
+    let mut reference = Versus::new(Ruleset::default(), SEED);
+    for frame in 0..FRAMES {
+        for (player, client) in clients.iter_mut().enumerate() {
+            for event in script(player, frame) {
+                client.run(event);
+                reference.run(player, event);
+            }
+            client.tick(TIMESTEP).unwrap();
+            assert_eq!(client.frame(), frame + 1);
+        }
+        reference.tick(TIMESTEP);
+    }
+    settle(&mut clients);
+
+    // the remote inputs were predicted then corrected
+    for client in &clients {
+        assert!(client.rollbacks() > 0);
+        assert_same(client.versus(), &reference);
+    }
+    assert!(reference.engine(0).get_display_state().stats.pieces > 10);
+}
+
+#[test]
+fn rollback_stalls_on_high_latency_test() {
+    // latency beyond the rollback window: the clients wait for each other
+    let mut clients = connect(MAX_ROLLBACK as u32 * 2, 6);
+    for frame in 0..FRAMES {
+        for (player, client) in clients.iter_mut().enumerate() {
+            for event in script(player, frame) {
+                client.run(event);
+            }
+            client.tick(TIMESTEP).unwrap();
+            assert!(client.frame() - client.confirmed_frame() <= MAX_ROLLBACK as u64);
+        }
+    }
+    assert!(clients[0].frame() < FRAMES);
+    settle(&mut clients);
+    assert_same(clients[0].versus(), clients[1].versus());
+}
+
+#[test]
+fn rollback_over_tcp_test() {
+    // the joiner plays the game sent by the host with the handshake
+    let ruleset = Ruleset {
+        scene_area: Position::new(8, 16),
+        ..Ruleset::default()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let host = std::thread::spawn(move || RollbackVersus::host(&listener, ruleset, SEED).unwrap());
+    let joiner = RollbackVersus::<TcpTransport>::join(address).unwrap();
+    let mut clients = [host.join().unwrap(), joiner];
+    assert_eq!(
+        (clients[0].local_player(), clients[1].local_player()),
+        (0, 1)
+    );
+    assert_same(clients[0].versus(), clients[1].versus());
+
+    for frame in 0..120 {
+        for (player, client) in clients.iter_mut().enumerate() {
+            for event in script(player, frame) {
+                client.run(event);
+            }
+            client.tick(TIMESTEP).unwrap();
+        }
+    }
+    while clients
+        .iter()
+        .any(|client| client.confirmed_frame() < client.frame() || client.frame() < 120)
+    {
+        for client in clients.iter_mut() {
+            client.tick(Duration::ZERO).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_same(clients[0].versus(), clients[1].versus());
+    let state = clients[1].versus().engine(0).get_display_state();
+    assert_eq!(state.scene_area, ruleset.scene_area);
+    assert!(state.stats.pieces > 0);
+}
+
+#[test]
+fn netcode_mismatch_test() {
+    // a lockstep client can't join a rollback game
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || RollbackVersus::host(&listener, Ruleset::default(), SEED));
+    let joined = NetVersus::join(address);
+    assert!(matches!(joined, Err(NetError::Protocol(_))));
+}
@@ -0,0 +1,110 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use tetris_core::tetris::Event;
+
+/// Action requested by the user with the keyboard
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Game(Event),
+    // start or stop the game session
+    Toggle,
+    Restart,
+    Quit,
+}
+
+/// Translation of the terminal key events into commands
+pub struct Input {
+    // true if the terminal reports key releases (so held keys are left to the engine DAS)
+    releases: bool,
+    // horizontal arrow key currently held, when releases are reported
+    held: Option<KeyCode>,
+}
+
+impl Input {
+    pub fn new(releases: bool) -> Self {
+        Self {
+            releases,
+            held: None,
+        }
+    }
+
+    /// Return the command of a key event, if any
+    pub fn command(&mut self, key: KeyEvent) -> Option<Command> {
+        match (key.code, key.kind) {
+            // horizontal moves: held (with DAS) when releases are reported,
+            // otherwise a move per key press, repeated by the terminal
+            (KeyCode::Left | KeyCode::Right, KeyEventKind::Press) if self.releases => {
+                self.held = Some(key.code);
+                Some(Command::Game(match key.code {
+                    KeyCode::Left => Event::ShiftLeft,
+                    _ => Event::ShiftRight,
+                }))
+            }
+            (KeyCode::Left | KeyCode::Right, KeyEventKind::Release) => {
+                (self.held == Some(key.code)).then(|| {
+                    self.held = None;
+                    Command::Game(Event::ShiftStop)
+                })
+            }
+            (_, KeyEventKind::Release) => None,
+            (_, KeyEventKind::Repeat) if self.releases && self.held == Some(key.code) => None,
+            (KeyCode::Left, _) => Some(Command::Game(Event::MoveLeft)),
+            (KeyCode::Right, _) => Some(Command::Game(Event::MoveRight)),
+            (KeyCode::Up, _) => Some(Command::Game(Event::Rotate)),
+            (KeyCode::Down, _) => Some(Command::Game(Event::MoveDown)),
+            (KeyCode::Char(' '), _) => Some(Command::Game(Event::Drop)),
+            (KeyCode::Char('c'), _) => Some(Command::Game(Event::Hold)),
+            (KeyCode::Char('p'), _) => Some(Command::Toggle),
+            (KeyCode::Char('r'), _) => Some(Command::Restart),
+            (KeyCode::Char('q') | KeyCode::Esc, _) => Some(Command::Quit),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{Command, Input};
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use tetris_core::tetris::Event;
+
+    fn key(code: KeyCode, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind)
+    }
+
+    #[test]
+    fn held_keys_test() {
+        let mut input = Input::new(true);
+        assert_eq!(
+            input.command(key(KeyCode::Left, KeyEventKind::Press)),
+            Some(Command::Game(Event::ShiftLeft))
+        );
+        // repeats are left to the engine, and only the held key release stops the shift
+        assert_eq!(
+            input.command(key(KeyCode::Left, KeyEventKind::Repeat)),
+            None
+        );
+        assert_eq!(
+            input.command(key(KeyCode::Right, KeyEventKind::Release)),
+            None
+        );
+        assert_eq!(
+            input.command(key(KeyCode::Left, KeyEventKind::Release)),
+            Some(Command::Game(Event::ShiftStop))
+        );
+    }
+
+    #[test]
+    fn pressed_keys_test() {
+        let mut input = Input::new(false);
+        for _ in 0..3 {
+            assert_eq!(
+                input.command(key(KeyCode::Right, KeyEventKind::Press)),
+                Some(Command::Game(Event::MoveRight))
+            );
+        }
+        assert_eq!(
+            input.command(key(KeyCode::Char(' '), KeyEventKind::Press)),
+            Some(Command::Game(Event::Drop))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{
+        self, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use input::{Command, Input};
+use std::io::{self, Write};
+use std::time::Duration;
+use tetris_core::{clock::SystemClock, ruleset::Ruleset, session::Session, tetris::Tetris};
+
+mod input;
+mod render;
+
+// time between two frames drawn on the terminal
+const FRAME: Duration = Duration::from_millis(16);
+
+fn main() -> io::Result<()> {
+    // set the terminal up: raw mode, alternate screen, and key releases if supported
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    // restore the terminal before a panic message is printed, so it is readable
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore(&mut io::stdout(), releases);
+        default_hook(info);
+    }));
+
+    let result = run(&mut stdout, releases);
+
+    // restore the terminal, even if the game failed
+    restore(&mut stdout, releases)?;
+    result
+}
+
+/// Bring the terminal back to its state before the game
+fn restore(out: &mut impl Write, releases: bool) -> io::Result<()> {
+    if releases {
+        execute!(out, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(out, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()
+}
+
+/// Main loop: run the keyboard commands, advance the game session and draw it
+fn run(out: &mut impl Write, releases: bool) -> io::Result<()> {
+    let mut session = Session::new(Tetris::new(Ruleset::default()), SystemClock::new());
+    let mut input = Input::new(releases);
+    session.start();
+    loop {
+        while event::poll(Duration::ZERO)? {
+            let event::Event::Key(key) = event::read()? else {
+                continue;
+            };
+            match input.command(key) {
+                Some(Command::Game(event)) => session.run(event),
+                Some(Command::Toggle) if session.is_running() => session.stop(),
+                Some(Command::Toggle) => session.start(),
+                Some(Command::Restart) => session.restart(),
+                Some(Command::Quit) => return Ok(()),
+                None => {}
+            }
+        }
+        session.update();
+
+        let state = session.engine().get_display_state();
+        let status = match (state.game_over, session.is_running()) {
+            (true, _) => "GAME OVER",
+            (false, false) => "PAUSED",
+            (false, true) => "",
+        };
+        render::draw(out, &state, status)?;
+        std::thread::sleep(FRAME);
+    }
+}
@@ -0,0 +1,141 @@
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use std::io::{self, Write};
+use tetris_core::{geometry::Position, highscores::MARATHON, tetris::DisplayState};
+
+/// Content of a cell of the game scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Player,
+    Projection,
+    Block,
+}
+
+impl Cell {
+    /// Return the text (two characters wide) and color drawing the cell
+    fn style(self) -> (&'static str, Color) {
+        match self {
+            Cell::Empty => (" .", Color::DarkGrey),
+            Cell::Player => ("██", Color::Yellow),
+            Cell::Projection => ("[]", Color::White),
+            Cell::Block => ("██", Color::DarkRed),
+        }
+    }
+}
+
+/// Return the grid of cells (rows of columns) of an area, filled with the layers of blocks,
+/// each layer covering the previous ones
+pub fn build_cells(area: Position, layers: &[(&[Position], Cell)]) -> Vec<Vec<Cell>> {
+    let mut cells = vec![vec![Cell::Empty; area.x as usize]; area.y as usize];
+    for &(blocks, cell) in layers {
+        for block in blocks {
+            if let Some(row) = cells.get_mut(block.y as usize) {
+                if let Some(target) = row.get_mut(block.x as usize) {
+                    *target = cell;
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Draw the game display state on the terminal, along with a status line
+pub fn draw(out: &mut impl Write, state: &DisplayState, status: &str) -> io::Result<()> {
+    let scene = build_cells(
+        state.scene_area,
+        &[
+            (&state.projection, Cell::Projection),
+            (&state.player, Cell::Player),
+            (&state.blocks, Cell::Block),
+        ],
+    );
+    let next = build_cells(Position::new(4, 4), &[(&state.next, Cell::Player)]);
+    let hold = build_cells(Position::new(4, 4), &[(&state.hold, Cell::Player)]);
+    let stats = &state.stats;
+    let panel = [
+        MARATHON.to_owned(),
+        String::new(),
+        format!("{:<10}Hold:", "Next:"),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        format!("Score: {}", state.score),
+        format!("Level: {}", state.level),
+        format!("Lines: {}", stats.lines()),
+        format!("PPS:   {:.2}", stats.pieces_per_second()),
+        String::new(),
+        status.to_owned(),
+        String::new(),
+        "arrows: move/rotate".to_owned(),
+        "space:  drop".to_owned(),
+        "c:      hold".to_owned(),
+        "p: pause  r: restart".to_owned(),
+        "q: quit".to_owned(),
+    ];
+    let panel_column = state.scene_area.x as u16 * 2 + 4;
+
+    for (y, row) in scene.iter().enumerate() {
+        queue!(out, MoveTo(0, y as u16), ResetColor, Print("<!"))?;
+        draw_row(out, row)?;
+        queue!(out, ResetColor, Print("!>"), Clear(ClearType::UntilNewLine))?;
+    }
+    let bottom = state.scene_area.y as u16;
+    queue!(
+        out,
+        MoveTo(0, bottom),
+        Print(format!("<!{}!>", "=".repeat(scene[0].len() * 2))),
+        Clear(ClearType::UntilNewLine),
+    )?;
+    for (y, line) in panel.iter().enumerate() {
+        queue!(out, MoveTo(panel_column, y as u16), ResetColor, Print(line))?;
+        queue!(out, Clear(ClearType::UntilNewLine))?;
+    }
+    for (y, (next, hold)) in next.iter().zip(&hold).enumerate() {
+        queue!(out, MoveTo(panel_column, 3 + y as u16))?;
+        draw_row(out, next)?;
+        queue!(out, MoveTo(panel_column + 10, 3 + y as u16))?;
+        draw_row(out, hold)?;
+    }
+    queue!(out, ResetColor)?;
+    out.flush()
+}
+
+/// Draw a row of cells at the cursor position
+fn draw_row(out: &mut impl Write, row: &[Cell]) -> io::Result<()> {
+    for cell in row {
+        let (text, color) = cell.style();
+        queue!(out, SetForegroundColor(color), Print(text))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{build_cells, Cell};
+    use tetris_core::geometry::Position;
+
+    #[test]
+    fn build_cells_test() {
+        let projection = [Position::new(0, 2), Position::new(1, 2)];
+        let player = [Position::new(1, 2), Position::new(5, 5)];
+        let cells = build_cells(
+            Position::new(3, 3),
+            &[(&projection, Cell::Projection), (&player, Cell::Player)],
+        );
+        assert_eq!(
+            cells,
+            vec![
+                vec![Cell::Empty; 3],
+                vec![Cell::Empty; 3],
+                vec![Cell::Projection, Cell::Player, Cell::Empty],
+            ]
+        );
+    }
+}